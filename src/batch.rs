@@ -0,0 +1,330 @@
+use crate::game::{Rule, RuleStats};
+use crate::puzzle::parse_puzzle;
+use crate::rule_file::RuleEntry;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Status {
+    Solved,
+    Stalled,
+    Contradiction,
+    Invalid,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Solved => "solved",
+            Status::Stalled => "stalled",
+            Status::Contradiction => "contradiction",
+            Status::Invalid => "invalid",
+        }
+    }
+}
+
+/// Outcome of running the rule engine on one puzzle of a batch.
+pub struct Report {
+    /// 1-based line number in the batch file.
+    pub line: usize,
+    pub puzzle: String,
+    pub status: Status,
+    /// Parse error of an invalid puzzle.
+    pub error: Option<String>,
+    pub firings: usize,
+    /// Elements modified by the built-in constraints rather than by rules.
+    pub builtin_changes: usize,
+    /// Percentage of the links decided by the rules.
+    pub decided: f64,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn parse(name: &str) -> Result<ReportFormat, String> {
+        match name {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unknown report format {name:?}")),
+        }
+    }
+}
+
+/// Puzzles of a batch file: one per line, skipping blank lines and `#`
+/// comments, paired with their line numbers.
+pub fn batch_puzzles(text: &str) -> Vec<(usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+/// Solves one puzzle with `rules` alone, without guessing, adding to `stats`
+/// what each rule did if given.
+pub fn solve_puzzle(
+    line: usize,
+    puzzle: &str,
+    rules: &[Rule],
+    stats: Option<&mut [RuleStats]>,
+) -> Report {
+    let start = Instant::now();
+    let mut report = Report {
+        line,
+        puzzle: puzzle.to_owned(),
+        status: Status::Invalid,
+        error: None,
+        firings: 0,
+        builtin_changes: 0,
+        decided: 0.0,
+        elapsed: Duration::ZERO,
+    };
+    match parse_puzzle(puzzle) {
+        Err(e) => report.error = Some(e),
+        Ok(mut game) => {
+            let result = match stats {
+                Some(stats) => game.propagate_profiled(rules, stats),
+                None => game.propagate(rules),
+            };
+            report.status = match result {
+                Err(_) => Status::Contradiction,
+                Ok(progress) => {
+                    report.firings = progress.firings;
+                    report.builtin_changes = progress.builtin_changes;
+                    if game.is_solved() {
+                        Status::Solved
+                    } else {
+                        Status::Stalled
+                    }
+                }
+            };
+            let (decided, total) = game.decided_links();
+            report.decided = 100.0 * decided as f64 / total as f64;
+        }
+    }
+    report.elapsed = start.elapsed();
+    report
+}
+
+/// Solves `puzzles` on `threads` worker threads sharing `rules`, and returns
+/// the reports in the order of `puzzles` whatever the scheduling. If given,
+/// `stats[i]` sums what `rules[i]` did over all the puzzles.
+pub fn solve_all(
+    puzzles: &[(usize, &str)],
+    rules: &[Rule],
+    threads: usize,
+    stats: Option<&mut [RuleStats]>,
+) -> Vec<Report> {
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(Vec::with_capacity(puzzles.len()));
+    let profiling = stats.is_some();
+    let stats = Mutex::new(stats);
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, puzzles.len().max(1)) {
+            scope.spawn(|| {
+                // Each thread profiles on its own and merges at the end.
+                let mut thread_stats = vec![RuleStats::default(); rules.len()];
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&(line, puzzle)) = puzzles.get(index) else {
+                        break;
+                    };
+                    let report = solve_puzzle(
+                        line,
+                        puzzle,
+                        rules,
+                        profiling.then_some(thread_stats.as_mut_slice()),
+                    );
+                    reports.lock().unwrap().push((index, report));
+                }
+                if let Some(stats) = stats.lock().unwrap().as_mut() {
+                    for (total, thread_total) in stats.iter_mut().zip(&thread_stats) {
+                        total.add(thread_total);
+                    }
+                }
+            });
+        }
+    });
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|&(index, _)| index);
+    reports.into_iter().map(|(_, report)| report).collect()
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+pub fn write_reports(
+    out: &mut impl Write,
+    reports: &[Report],
+    format: ReportFormat,
+) -> io::Result<()> {
+    match format {
+        ReportFormat::Csv => {
+            writeln!(
+                out,
+                "line,puzzle,status,firings,builtin_changes,decided_percent,elapsed_ms,error"
+            )?;
+            for report in reports {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{:.1},{:.3},{}",
+                    report.line,
+                    csv_field(&report.puzzle),
+                    report.status.name(),
+                    report.firings,
+                    report.builtin_changes,
+                    report.decided,
+                    report.elapsed.as_secs_f64() * 1000.0,
+                    csv_field(report.error.as_deref().unwrap_or(""))
+                )?;
+            }
+        }
+        ReportFormat::Json => {
+            writeln!(out, "[")?;
+            for (index, report) in reports.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  {{\"line\": {}, \"puzzle\": {}, \"status\": \"{}\", \"firings\": {}, \
+                     \"builtin_changes\": {}, \"decided_percent\": {:.1}, \"elapsed_ms\": {:.3}, \"error\": {}}}{}",
+                    report.line,
+                    json_string(&report.puzzle),
+                    report.status.name(),
+                    report.firings,
+                    report.builtin_changes,
+                    report.decided,
+                    report.elapsed.as_secs_f64() * 1000.0,
+                    report
+                        .error
+                        .as_deref()
+                        .map_or("null".to_owned(), json_string),
+                    if index + 1 < reports.len() { "," } else { "" }
+                )?;
+            }
+            writeln!(out, "]")?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes one line per status with the number of puzzles, followed by the
+/// time spent solving, summed over the threads.
+pub fn write_summary(out: &mut impl Write, reports: &[Report]) -> io::Result<()> {
+    for status in [
+        Status::Solved,
+        Status::Stalled,
+        Status::Contradiction,
+        Status::Invalid,
+    ] {
+        let count = reports
+            .iter()
+            .filter(|report| report.status == status)
+            .count();
+        writeln!(out, "{:>13}: {count}", status.name())?;
+    }
+    let total = reports
+        .iter()
+        .map(|report| report.elapsed)
+        .sum::<Duration>();
+    writeln!(
+        out,
+        "{:>13}: {} puzzles, {:.3} s of solving",
+        "total",
+        reports.len(),
+        total.as_secs_f64()
+    )
+}
+
+/// Writes one line per rule of `entries` with what its variants did, summed
+/// from `stats` (one per variant, in the order of
+/// [`crate::rule_file::flatten`]), most useful rules first: by number of
+/// changes, then by time. The variants column counts the variants, that is the
+/// symmetries, that changed the board at least once. A rule with several
+/// variants is followed by an indented line for each of them, named after
+/// the symmetry that produced it.
+pub fn write_profile(
+    out: &mut impl Write,
+    entries: &[RuleEntry],
+    stats: &[RuleStats],
+) -> io::Result<()> {
+    let mut rows = Vec::new();
+    let mut variants = stats;
+    for entry in entries {
+        let (entry_stats, rest) = variants.split_at(entry.variants.len());
+        variants = rest;
+        let mut total = RuleStats::default();
+        for variant in entry_stats {
+            total.add(variant);
+        }
+        let useful = entry_stats
+            .iter()
+            .filter(|variant| variant.changed > 0)
+            .count();
+        rows.push((entry, total, useful, entry_stats));
+    }
+    rows.sort_by(|(_, a, ..), (_, b, ..)| {
+        b.changed.cmp(&a.changed).then(a.elapsed.cmp(&b.elapsed))
+    });
+    writeln!(
+        out,
+        "{:>9}  {:>9}  {:>11}  {:>10}  {:>8}  {:>8}  name",
+        "changed", "matched", "tried", "time_ms", "variants", "priority"
+    )?;
+    let write_row =
+        |out: &mut dyn Write, stats: &RuleStats, variants: &str, priority: &str, name: &str| {
+            writeln!(
+                out,
+                "{:>9}  {:>9}  {:>11}  {:>10.3}  {:>8}  {:>8}  {}",
+                stats.changed,
+                stats.matched,
+                stats.tried,
+                stats.elapsed.as_secs_f64() * 1000.0,
+                variants,
+                priority,
+                name
+            )
+        };
+    for (entry, total, useful, entry_stats) in rows {
+        write_row(
+            out,
+            &total,
+            &format!("{useful}/{}", entry.variants.len()),
+            &entry.priority.to_string(),
+            entry.rule().name(),
+        )?;
+        if entry.variants.len() > 1 {
+            let names = entry.rule().named_orbit(entry.symmetry);
+            for ((name, _), variant) in names.iter().zip(entry_stats) {
+                write_row(out, variant, "", "", &format!("  {name}"))?;
+            }
+        }
+    }
+    Ok(())
+}
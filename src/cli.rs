@@ -0,0 +1,591 @@
+use crate::batch::{
+    ReportFormat, batch_puzzles, solve_all, write_profile, write_reports, write_summary,
+};
+use crate::game::{Game, Rule, RuleStats};
+use crate::generate::{Rng, clued_loop, generate, grid};
+use crate::play::play;
+use crate::puzzle::{parse_puzzle, puzzle_id};
+use crate::rule_file::{
+    DEFAULT_RULE_FILES, RuleEntry, RuleSelection, check_names, flatten, lint, load_rule_files,
+};
+use crate::sat::{SatSolver, sat_solutions};
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_NO_SOLUTION: i32 = 2;
+pub const EXIT_MULTIPLE_SOLUTIONS: i32 = 3;
+pub const EXIT_STUCK: i32 = 4;
+
+const USAGE: &str = "\
+Usage: slitherlinker <command> [options]
+
+Commands:
+  solve [PUZZLE]        Solve a puzzle given as argument, with --file, or on stdin
+  grade [PUZZLE]        Report the rules needed to solve a puzzle
+  generate WxH          Generate a puzzle that the rules solve without guessing
+  play [PUZZLE]         Play or edit a puzzle interactively
+  batch [FILE]          Solve every puzzle of FILE (one per line) with the rules
+                        alone, and report on each of them
+  bench [WxH]           Time rule matching on random boards (default: 30x30)
+  cnf [PUZZLE]          Print the puzzle as a DIMACS CNF formula, one variable
+                        per link, without the single loop constraint
+  rules list            List the loaded rules, with the id of their canonical form
+  rules verify          Check that the loaded rules are locally sound
+  rules lint            Report loaded rules that do nothing, widen their input,
+                        have a window larger than needed, share their name
+                        or their canonical form with another rule
+  rules render [NAME]   Render the loaded rules, or only the one named NAME
+
+Puzzles are game IDs such as 5x5:b2b2a1d222a202b333a, or grids with one row
+per line (or rows separated by '/'), using 0-3 for clues and '.' for blanks.
+
+Options:
+  --rules FILE[=PRIORITY]  Load rules from FILE instead of the default rule files;
+                           may be repeated (default priority: 200); rule files
+                           may include others with [INCLUDE] PATH
+  --enable TAGS            Only use the rules with one of the comma-separated TAGS;
+                           rules are tagged with the name of their file, such as
+                           basic or unconditional, and with the tags of [TAGS]
+  --disable TAGS           Leave out the rules with one of the comma-separated TAGS
+  --max-priority N         Leave out the rules of priority above N
+  --file FILE              Read the puzzle from FILE ('-' for stdin)
+  --render STYLE           clues, ascii, full, unicode, color or svg
+  --rules-only             solve: stop instead of guessing when the rules get stuck
+  --sat SOLVER             solve: use 'builtin' or a MiniSat-compatible program
+                           instead of the rules, and cross-check with the rules
+  --seed N                 generate, bench: seed of the random generator
+  --format FORMAT          batch: csv or json (default: csv)
+  --threads N              batch: number of worker threads (default: one per CPU)
+  --profile                batch: also print to stderr what each rule did, as
+                           anchors tried, matched and changed and time spent,
+                           and below it what each of its symmetric variants did
+
+Exit codes: 0 success, 1 usage or input error, 2 no solution,
+            3 multiple solutions, 4 stuck (with --rules-only)";
+
+/// Why a command failed.
+#[derive(Debug)]
+enum Failure {
+    Message(String),
+    /// Writing the output failed, which is no error if its reader is gone,
+    /// as in `slitherlinker rules list | head`.
+    Output(io::Error),
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Failure {
+        Failure::Message(message)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Failure {
+        Failure::Output(e)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum Renderer {
+    Clues,
+    Ascii,
+    Full,
+    Unicode,
+    Color,
+    Svg,
+}
+
+impl Renderer {
+    fn parse(name: &str) -> Result<Renderer, String> {
+        Ok(match name {
+            "clues" => Renderer::Clues,
+            "ascii" => Renderer::Ascii,
+            "full" => Renderer::Full,
+            "unicode" => Renderer::Unicode,
+            "color" => Renderer::Color,
+            "svg" => Renderer::Svg,
+            _ => return Err(format!("unknown renderer {name:?}")),
+        })
+    }
+
+    fn write_game(self, out: &mut impl Write, game: &Game) -> io::Result<()> {
+        match self {
+            Renderer::Clues => game.render_cells(out),
+            Renderer::Ascii => game.render_cells_and_links(out),
+            Renderer::Full => game.full_render(out),
+            Renderer::Unicode => game.render_unicode(out, false, None),
+            Renderer::Color => game.render_unicode(out, true, None),
+            Renderer::Svg => write!(out, "{}", game.to_svg(false)),
+        }
+    }
+
+    fn write_rule(self, out: &mut impl Write, rule: &Rule) -> io::Result<()> {
+        match self {
+            Renderer::Svg => write!(out, "{}", rule.to_svg()),
+            _ => rule.render(out),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    positional: Vec<String>,
+    rule_files: Vec<(String, i32)>,
+    selection: RuleSelection,
+    file: Option<String>,
+    render: Option<Renderer>,
+    rules_only: bool,
+    seed: Option<u64>,
+    format: Option<ReportFormat>,
+    threads: Option<usize>,
+    profile: bool,
+    sat: Option<SatSolver>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--rules" => {
+                    let spec = value()?;
+                    let (path, priority) = match spec.rsplit_once('=') {
+                        Some((path, priority)) => (
+                            path,
+                            priority
+                                .parse()
+                                .map_err(|_| format!("invalid priority in {spec:?}"))?,
+                        ),
+                        None => (spec.as_str(), 200),
+                    };
+                    options.rule_files.push((path.to_owned(), priority));
+                }
+                "--enable" => {
+                    let tags = value()?.split(',').map(str::to_owned);
+                    options.selection.enabled.extend(tags);
+                }
+                "--disable" => {
+                    let tags = value()?.split(',').map(str::to_owned);
+                    options.selection.disabled.extend(tags);
+                }
+                "--max-priority" => {
+                    let priority = value()?;
+                    options.selection.max_priority = Some(
+                        priority
+                            .parse()
+                            .map_err(|_| format!("invalid priority {priority:?}"))?,
+                    );
+                }
+                "--file" => options.file = Some(value()?.clone()),
+                "--render" => options.render = Some(Renderer::parse(value()?)?),
+                "--rules-only" => options.rules_only = true,
+                "--profile" => options.profile = true,
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed =
+                        Some(seed.parse().map_err(|_| format!("invalid seed {seed:?}"))?);
+                }
+                "--format" => options.format = Some(ReportFormat::parse(value()?)?),
+                "--sat" => options.sat = Some(SatSolver::parse(value()?)),
+                "--threads" => {
+                    let threads = value()?;
+                    options.threads = Some(
+                        threads
+                            .parse()
+                            .ok()
+                            .filter(|&threads| threads > 0)
+                            .ok_or_else(|| format!("invalid thread count {threads:?}"))?,
+                    );
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+                _ => options.positional.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+
+    /// The rules of the rule files selected by `--enable`, `--disable` and
+    /// `--max-priority`, without checking that their names are unique.
+    fn unchecked_rules(&self) -> Result<Vec<RuleEntry>, String> {
+        let entries = if self.rule_files.is_empty() {
+            load_rule_files(DEFAULT_RULE_FILES)?
+        } else {
+            load_rule_files(
+                self.rule_files
+                    .iter()
+                    .map(|(path, priority)| (path.as_str(), *priority)),
+            )?
+        };
+        self.selection.apply(entries)
+    }
+
+    fn rules(&self) -> Result<Vec<RuleEntry>, String> {
+        let entries = self.unchecked_rules()?;
+        check_names(&entries)?;
+        Ok(entries)
+    }
+
+    /// The text of the file given by `--file`, or else the positional argument
+    /// at `positional` (read as a file name if `is_path`), or else stdin. A
+    /// file name of `-` also stands for stdin.
+    fn input(&self, positional: usize, is_path: bool) -> Result<String, String> {
+        let path = match (&self.file, self.positional.get(positional)) {
+            (Some(path), _) => Some(path),
+            (None, Some(arg)) if is_path => Some(arg),
+            (None, Some(arg)) if arg != "-" => return Ok(arg.clone()),
+            _ => None,
+        };
+        match path {
+            Some(path) if path != "-" => {
+                fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))
+            }
+            _ => {
+                let mut text = String::new();
+                io::stdin()
+                    .read_to_string(&mut text)
+                    .map_err(|e| format!("cannot read stdin: {e}"))?;
+                Ok(text)
+            }
+        }
+    }
+
+    fn puzzle(&self, positional: usize) -> Result<Game, String> {
+        parse_puzzle(&self.input(positional, false)?)
+    }
+
+    /// Board size given as `WxH` by the first positional argument.
+    fn size(&self, default: Option<&str>) -> Result<(usize, usize), String> {
+        let size = self
+            .positional
+            .first()
+            .map(String::as_str)
+            .or(default)
+            .ok_or("missing size such as 7x7")?;
+        size.split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .filter(|&(width, height)| width >= 5 && height >= 5)
+            .ok_or_else(|| format!("invalid size {size:?}, expected WxH with W, H >= 5"))
+    }
+
+    fn rng(&self) -> Rng {
+        Rng::new(self.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |duration| duration.as_nanos() as u64)
+        }))
+    }
+}
+
+/// Links of the board as text, to compare boards.
+fn board_text(game: &Game) -> Vec<u8> {
+    let mut text = Vec::new();
+    game.render_cells_and_links(&mut text).unwrap();
+    text
+}
+
+fn solve(options: &Options) -> Result<i32, Failure> {
+    let mut out = io::stdout().lock();
+    let rules = flatten(&options.rules()?);
+    let mut game = options.puzzle(0)?;
+    let render = options.render.unwrap_or(Renderer::Unicode);
+    if options.rules_only {
+        return Ok(match game.propagate(&rules) {
+            Err(_) => {
+                writeln!(out, "No solution: the rules reach a contradiction")?;
+                EXIT_NO_SOLUTION
+            }
+            Ok(_) if game.is_solved() => {
+                render.write_game(&mut out, &game)?;
+                EXIT_OK
+            }
+            Ok(_) => {
+                render.write_game(&mut out, &game)?;
+                writeln!(out, "Stuck: the rules cannot decide every link")?;
+                EXIT_STUCK
+            }
+        });
+    }
+    let mut solutions = game.search(&rules, 2).solutions;
+    if let Some(solver) = &options.sat {
+        let sat = sat_solutions(&game, solver, 2)?;
+        let unique_differs = sat.len() == 1
+            && solutions.len() == 1
+            && board_text(&sat[0]) != board_text(&solutions[0]);
+        if sat.len() != solutions.len() || unique_differs {
+            eprintln!("warning: the rules and the SAT solver disagree");
+        }
+        solutions = sat;
+    }
+    Ok(match solutions.as_slice() {
+        [] => {
+            writeln!(out, "No solution")?;
+            EXIT_NO_SOLUTION
+        }
+        [solution] => {
+            render.write_game(&mut out, solution)?;
+            EXIT_OK
+        }
+        [solution, ..] => {
+            render.write_game(&mut out, solution)?;
+            writeln!(out, "Multiple solutions, the one above is only one of them")?;
+            EXIT_MULTIPLE_SOLUTIONS
+        }
+    })
+}
+
+fn grade(options: &Options) -> Result<i32, Failure> {
+    let mut out = io::stdout().lock();
+    let entries = options.rules()?;
+    let rules = flatten(&entries);
+    let mut game = options.puzzle(0)?;
+    let outcome = game.search(&rules, 2);
+    match outcome.solutions.len() {
+        0 => {
+            writeln!(out, "No solution")?;
+            return Ok(EXIT_NO_SOLUTION);
+        }
+        1 => (),
+        _ => {
+            writeln!(out, "Multiple solutions")?;
+            return Ok(EXIT_MULTIPLE_SOLUTIONS);
+        }
+    }
+    let mut priorities = entries
+        .iter()
+        .map(|entry| entry.priority)
+        .collect::<Vec<_>>();
+    priorities.dedup();
+    for priority in priorities {
+        let rules = entries
+            .iter()
+            .filter(|entry| entry.priority <= priority)
+            .flat_map(|entry| entry.variants.iter().cloned())
+            .collect::<Vec<_>>();
+        let mut game = game.clone();
+        if game.propagate(&rules).is_ok() && game.is_solved() {
+            writeln!(out, "Solved by rules of priority up to {priority}")?;
+            return Ok(EXIT_OK);
+        }
+    }
+    writeln!(
+        out,
+        "Requires guessing beyond the rules ({} guesses)",
+        outcome.guesses
+    )?;
+    Ok(EXIT_OK)
+}
+
+fn generate_command(options: &Options) -> Result<i32, Failure> {
+    let mut out = io::stdout().lock();
+    let (width, height) = options.size(None)?;
+    let rules = flatten(&options.rules()?);
+    let game = generate(width, height, &rules, &mut options.rng());
+    writeln!(out, "{}", puzzle_id(&game))?;
+    if let Some(render) = options.render {
+        render.write_game(&mut out, &game)?;
+    }
+    Ok(EXIT_OK)
+}
+
+/// Times rule propagation, indexed and incremental, against sweeping every
+/// rule over every anchor, on a fully clued random loop and on the same loop
+/// with half of the clues removed.
+fn bench(options: &Options) -> Result<i32, Failure> {
+    let mut out = io::stdout().lock();
+    let (width, height) = options.size(Some("30x30"))?;
+    let rules = flatten(&options.rules()?);
+    let mut rng = options.rng();
+    let full = clued_loop(width, height, &mut rng);
+    let mut half = full.clone();
+    for row in half.iter_mut() {
+        for clue in row.iter_mut() {
+            if rng.below(2) == 0 {
+                *clue = '.';
+            }
+        }
+    }
+    for (label, clues) in [("all clues", full), ("half of the clues", half)] {
+        let puzzle = grid(&clues);
+        let mut brute_force = parse_puzzle(&puzzle)?;
+        let start = Instant::now();
+        let brute_force_firings = brute_force.propagate_brute_force(&rules).ok();
+        let brute_force_time = start.elapsed();
+        let mut indexed = parse_puzzle(&puzzle)?;
+        let start = Instant::now();
+        let indexed_firings = indexed.propagate(&rules).ok();
+        let indexed_time = start.elapsed();
+        if (brute_force_firings.is_some(), board_text(&brute_force))
+            != (indexed_firings.is_some(), board_text(&indexed))
+        {
+            return Err(format!("propagation results differ on {puzzle}").into());
+        }
+        writeln!(
+            out,
+            "{width}x{height}, {label}: brute force {:.3} s, propagate {:.3} s ({:.1}x), {:.1}% of links decided",
+            brute_force_time.as_secs_f64(),
+            indexed_time.as_secs_f64(),
+            brute_force_time.as_secs_f64() / indexed_time.as_secs_f64(),
+            {
+                let (decided, total) = indexed.decided_links();
+                100.0 * decided as f64 / total as f64
+            }
+        )?;
+    }
+    Ok(EXIT_OK)
+}
+
+fn batch(options: &Options) -> Result<i32, Failure> {
+    let mut out = io::stdout().lock();
+    let entries = options.rules()?;
+    let rules = flatten(&entries);
+    let text = options.input(0, true)?;
+    let threads = options
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let mut stats = vec![RuleStats::default(); rules.len()];
+    let reports = solve_all(
+        &batch_puzzles(&text),
+        &rules,
+        threads,
+        options.profile.then_some(stats.as_mut_slice()),
+    );
+    let format = options.format.unwrap_or(ReportFormat::Csv);
+    write_reports(&mut out, &reports, format)?;
+    write_summary(&mut io::stderr().lock(), &reports)?;
+    if options.profile {
+        write_profile(&mut io::stderr().lock(), &entries, &stats)?;
+    }
+    Ok(EXIT_OK)
+}
+
+fn cnf(options: &Options) -> Result<i32, Failure> {
+    let mut out = io::stdout().lock();
+    let game = options.puzzle(0)?;
+    game.to_cnf().write_dimacs(&mut out)?;
+    Ok(EXIT_OK)
+}
+
+fn rules_command(options: &Options) -> Result<i32, Failure> {
+    let mut out = io::stdout().lock();
+    if options
+        .positional
+        .first()
+        .is_some_and(|command| command == "lint")
+    {
+        let problems = lint(&options.unchecked_rules()?);
+        for problem in &problems {
+            writeln!(out, "{problem}")?;
+        }
+        return Ok(if problems.is_empty() {
+            EXIT_OK
+        } else {
+            EXIT_ERROR
+        });
+    }
+    let entries = options.rules()?;
+    match options.positional.first().map(String::as_str) {
+        Some("list") => {
+            writeln!(
+                out,
+                "priority  variants  symmetry        tags                  id                name"
+            )?;
+            for entry in &entries {
+                writeln!(
+                    out,
+                    "{:>8}  {:>8}  {:<14}  {:<20}  {:016x}  {}",
+                    entry.priority,
+                    entry.variants.len(),
+                    entry.symmetry.name(),
+                    entry.tags.join(","),
+                    entry.rule().canonical_hash(entry.symmetry),
+                    entry.rule().name()
+                )?;
+            }
+            Ok(EXIT_OK)
+        }
+        Some("verify") => {
+            let mut status = EXIT_OK;
+            for entry in &entries {
+                match entry.rule().verify() {
+                    Ok(()) => writeln!(out, "ok      {}", entry.rule().name())?,
+                    Err(e) => {
+                        writeln!(out, "FAILED  {}: {e}", entry.rule().name())?;
+                        status = EXIT_ERROR;
+                    }
+                }
+            }
+            Ok(status)
+        }
+        Some("render") => {
+            let render = options.render.unwrap_or(Renderer::Ascii);
+            let name = options.positional.get(1);
+            let mut found = false;
+            for entry in &entries {
+                if name.is_none_or(|name| name == entry.rule().name()) {
+                    render.write_rule(&mut out, entry.rule())?;
+                    found = true;
+                }
+            }
+            if !found {
+                return Err(format!("no rule named {:?}", name.unwrap()).into());
+            }
+            Ok(EXIT_OK)
+        }
+        _ => Err(
+            "expected rules list, rules verify, rules lint or rules render"
+                .to_owned()
+                .into(),
+        ),
+    }
+}
+
+/// Runs the command line `args` (without the program name) and returns the
+/// exit code.
+pub fn run(args: &[String]) -> i32 {
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        return EXIT_ERROR;
+    };
+    let result = Options::parse(rest)
+        .map_err(Failure::from)
+        .and_then(|options| match command.as_str() {
+            "solve" => solve(&options),
+            "grade" => grade(&options),
+            "generate" => generate_command(&options),
+            "play" => {
+                let rules = flatten(&options.rules()?);
+                let mut game = options.puzzle(0)?;
+                play(&mut game, &rules).map_err(|e| e.to_string())?;
+                Ok(EXIT_OK)
+            }
+            "batch" => batch(&options),
+            "bench" => bench(&options),
+            "cnf" => cnf(&options),
+            "rules" => rules_command(&options),
+            "help" | "--help" | "-h" => {
+                writeln!(io::stdout().lock(), "{USAGE}")?;
+                Ok(EXIT_OK)
+            }
+            _ => Err(format!("unknown command {command:?}\n\n{USAGE}").into()),
+        });
+    match result {
+        Ok(code) => code,
+        Err(Failure::Output(e)) if e.kind() == ErrorKind::BrokenPipe => EXIT_OK,
+        Err(Failure::Output(e)) => {
+            eprintln!("error: cannot write the output: {e}");
+            EXIT_ERROR
+        }
+        Err(Failure::Message(e)) => {
+            eprintln!("error: {e}");
+            EXIT_ERROR
+        }
+    }
+}
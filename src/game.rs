@@ -1,15 +1,59 @@
+mod canonical;
+mod cnf;
+mod colors;
+mod corner;
 mod element;
+mod grid;
+mod journal;
+mod labels;
+mod lint;
+mod matcher;
+mod parity;
+mod propagate;
 mod rule;
+mod solve;
+mod svg;
+mod unicode;
+mod verify;
+mod vertex;
+pub use cnf::Cnf;
+use colors::ColorClasses;
 use element::*;
-pub use rule::Rule;
+pub use element::{CellType, LinkType};
+use grid::Grid;
+use journal::Change;
+pub use journal::Checkpoint;
+pub use propagate::RuleStats;
+pub use rule::{Rule, Symmetry};
+use std::io::{self, Write};
+pub use unicode::ClueStatus;
 
+/// The board has reached a state that no solution can satisfy.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Contradiction;
+
+#[derive(Clone)]
 pub struct Game {
     height: usize,
     width: usize,
-    cells: Vec<Vec<CellType>>,
-    hlinks: Vec<Vec<LinkType>>,
-    vlinks: Vec<Vec<LinkType>>,
-    corners: Vec<Vec<CornerType>>,
+    cells: Grid<CellType>,
+    colors: Grid<ColorType>,
+    hlinks: Grid<LinkType>,
+    vlinks: Grid<LinkType>,
+    corners: Grid<CornerType>,
+    vertices: Grid<VertexType>,
+    /// Window `(row, col, height, width)` of the last rule application that
+    /// modified the board.
+    last_applied: Option<(usize, usize, usize, usize)>,
+    journal: Vec<Change>,
+    /// Whether each change of the journal was made with a public setter,
+    /// rather than deduced.
+    edits: Vec<bool>,
+    redo_stack: Vec<(Vec<Change>, Vec<bool>)>,
+    color_classes: ColorClasses,
+    /// Length of the journal already examined by [`Game::constrain_colors`],
+    /// or `None` if the colour classes must be rebuilt from the whole board.
+    colors_checked: Option<usize>,
 }
 
 impl Game {
@@ -52,9 +96,18 @@ impl Game {
         cells.push(vec![Empty; width + 2]);
         width += 2;
         height += 2;
-        let mut hlinks = vec![vec![LMaybe; width]; height + 1];
-        let mut vlinks = vec![vec![LMaybe; width + 1]; height];
-        let mut corners = vec![vec![CMaybe; 2 * width]; 2 * height];
+        let mut colors = Grid::new(height, width, Unknown);
+        let mut hlinks = Grid::new(height + 1, width, LMaybe);
+        let mut vlinks = Grid::new(height, width + 1, LMaybe);
+        let mut corners = Grid::new(2 * height, 2 * width, CMaybe);
+        for row in 0..height {
+            colors[row][0] = Outside;
+            colors[row][width - 1] = Outside;
+        }
+        for col in 0..width {
+            colors[0][col] = Outside;
+            colors[height - 1][col] = Outside;
+        }
         for row in 0..=height {
             hlinks[row][0] = Unlink;
             hlinks[row][width - 1] = Unlink;
@@ -83,202 +136,329 @@ impl Game {
             corners[0][2 * col + 1] = CZero;
             corners[2 * height - 1][2 * col + 1] = CZero;
         }
+        let mut vertices = Grid::new(height + 1, width + 1, VMaybe);
+        for row in 0..=height {
+            vertices[row][0] = Unvisited;
+            vertices[row][width] = Unvisited;
+        }
+        for col in 0..=width {
+            vertices[0][col] = Unvisited;
+            vertices[height][col] = Unvisited;
+        }
         Game {
             width,
             height,
-            cells,
+            cells: cells.into(),
+            colors,
             hlinks,
             vlinks,
             corners,
+            vertices,
+            last_applied: None,
+            journal: Vec::new(),
+            edits: Vec::new(),
+            redo_stack: Vec::new(),
+            color_classes: ColorClasses::new(height * width),
+            colors_checked: None,
         }
     }
 
-    pub fn print_cells(&self) {
-        println!();
-        print!("+");
+    /// Encodes the clues in the format accepted by [`Game::new`].
+    pub fn task(&self) -> String {
+        let mut task = String::new();
+        let mut blanks = 0;
+        let flush = |task: &mut String, blanks: &mut usize| {
+            while *blanks > 0 {
+                let run = (*blanks).min(26);
+                task.push((b'a' + run as u8 - 1) as char);
+                *blanks -= run;
+            }
+        };
+        for row in 1..(self.height - 1) {
+            for col in 1..(self.width - 1) {
+                match self.cells[row][col] {
+                    Empty => blanks += 1,
+                    cell => {
+                        flush(&mut task, &mut blanks);
+                        task.push(cell.to_char());
+                    }
+                }
+            }
+        }
+        flush(&mut task, &mut blanks);
+        task
+    }
+
+    pub fn render_cells(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out)?;
+        write!(out, "+")?;
         for _ in 1..(self.width - 1) {
-            print!("--");
+            write!(out, "--")?;
         }
-        println!("-+");
+        writeln!(out, "-+")?;
         for row in 1..(self.height - 1) {
-            print!("|");
+            write!(out, "|")?;
             for col in 1..(self.width - 1) {
-                print!(
-                    " {}",
-                    match self.cells[row][col] {
-                        Empty => ' ',
-                        Zero => '0',
-                        One => '1',
-                        Two => '2',
-                        Three => '3',
-                    }
-                );
+                write!(out, " {}", self.cells[row][col].to_char())?;
             }
-            println!(" |");
+            writeln!(out, " |")?;
         }
-        print!("+");
+        write!(out, "+")?;
         for _ in 1..(self.width - 1) {
-            print!("--");
+            write!(out, "--")?;
         }
-        println!("-+");
-        println!();
+        writeln!(out, "-+")?;
+        writeln!(out)
     }
 
-    pub fn print_cells_and_links(&self) {
-        let print_row_hlinks = |row: usize| {
-            print!(" ");
+    #[allow(dead_code)]
+    pub fn print_cells(&self) {
+        self.render_cells(&mut io::stdout().lock()).unwrap();
+    }
+
+    pub fn render_cells_and_links<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let render_row_hlinks = |out: &mut W, row: usize| -> io::Result<()> {
+            write!(out, " ")?;
             for col in 1..(self.width - 1) {
-                print!(
+                write!(
+                    out,
                     "+{}",
                     match self.hlinks[row][col] {
                         LMaybe => "...",
                         Link => "---",
                         Unlink => "   ",
                     }
-                );
+                )?;
             }
-            println!("+");
+            writeln!(out, "+")
         };
-        println!();
+        let render_vlink = |out: &mut W, row: usize, col: usize| {
+            write!(
+                out,
+                "{}",
+                match self.vlinks[row][col] {
+                    LMaybe => ".",
+                    Link => "|",
+                    Unlink => " ",
+                }
+            )
+        };
+        writeln!(out)?;
         for row in 1..(self.height - 1) {
             if row == 1 {
-                print_row_hlinks(1);
+                render_row_hlinks(out, 1)?;
             }
-            let print_vlink = |col: usize| {
-                print!(
-                    "{}",
-                    match self.vlinks[row][col] {
-                        LMaybe => ".",
-                        Link => "|",
-                        Unlink => " ",
-                    }
-                );
-            };
-            print!(" ");
+            write!(out, " ")?;
             for col in 1..(self.width - 1) {
                 if col == 1 {
-                    print_vlink(1);
+                    render_vlink(out, row, 1)?;
                 }
-                print!(
-                    " {} ",
-                    match self.cells[row][col] {
-                        Empty => ' ',
-                        Zero => '0',
-                        One => '1',
-                        Two => '2',
-                        Three => '3',
-                    }
-                );
-                print_vlink(col + 1);
+                write!(out, " {} ", self.cells[row][col].to_char())?;
+                render_vlink(out, row, col + 1)?;
             }
-            println!();
-            print_row_hlinks(row + 1);
+            writeln!(out)?;
+            render_row_hlinks(out, row + 1)?;
         }
-        println!();
+        writeln!(out)
     }
 
-    pub fn full_print(&self) {
+    #[allow(dead_code)]
+    pub fn print_cells_and_links(&self) {
+        self.render_cells_and_links(&mut io::stdout().lock())
+            .unwrap();
+    }
+
+    pub fn full_render(&self, out: &mut impl Write) -> io::Result<()> {
         for row in 4..(4 * self.height - 3) {
-            full_print_row(
+            full_render_row(
+                out,
                 self.height,
                 self.width,
                 &self.cells,
+                &self.colors,
                 &self.hlinks,
                 &self.vlinks,
                 &self.corners,
                 row,
                 true,
-            );
-            println!();
+            )?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn full_print(&self) {
+        self.full_render(&mut io::stdout().lock()).unwrap();
+    }
+
+    /// Height of the board, including the padding.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Width of the board, including the padding.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Whether the cell at `(row, col)` is part of the padding around the
+    /// puzzle.
+    pub(super) fn is_padding(&self, row: usize, col: usize) -> bool {
+        row == 0 || col == 0 || row == self.height - 1 || col == self.width - 1
+    }
+
+    pub fn hlink(&self, row: usize, col: usize) -> LinkType {
+        self.hlinks[row][col]
+    }
+
+    pub fn vlink(&self, row: usize, col: usize) -> LinkType {
+        self.vlinks[row][col]
+    }
+
+    // The setters below are edits of the player. An edit that is not a
+    // narrowing, such as clearing a link, may invalidate what was deduced
+    // from the previous value, so the board then goes back to its clues and
+    // edits, see `Game::forget_deductions`.
+
+    pub fn set_cell(&mut self, row: usize, col: usize, cell: CellType) {
+        let widened = cell.to_mask() & !self.cells[row][col].to_mask() != 0;
+        if self.write_cell(row, col, cell) {
+            self.mark_edit(widened);
+        }
+    }
+
+    pub fn set_hlink(&mut self, row: usize, col: usize, link: LinkType) {
+        let widened = link.to_mask() & !self.hlinks[row][col].to_mask() != 0;
+        if self.write_hlink(row, col, link) {
+            self.mark_edit(widened);
+        }
+    }
+
+    pub fn set_vlink(&mut self, row: usize, col: usize, link: LinkType) {
+        let widened = link.to_mask() & !self.vlinks[row][col].to_mask() != 0;
+        if self.write_vlink(row, col, link) {
+            self.mark_edit(widened);
         }
     }
 
-    pub fn try_apply_rule(&mut self, rule: &Rule, row_s: usize, col_s: usize) -> Option<bool> {
+    /// Returns `Ok(None)` if the rule does not match at the anchor, and
+    /// otherwise whether applying it modified the board. A matching rule whose
+    /// output is incompatible with the board is a contradiction, in which case
+    /// the board is left untouched.
+    ///
+    /// The window may overlap the padding, whose unlinked links, zero corners
+    /// and outside colours are facts like any decided element, so a rule that
+    /// holds inside the grid also holds there. Rules that only hold at the
+    /// edge mark the cells of the window that must lie outside the grid.
+    pub fn try_apply_rule(
+        &mut self,
+        rule: &Rule,
+        row_s: usize,
+        col_s: usize,
+    ) -> Result<Option<bool>, Contradiction> {
         let height = self.height;
         let width = self.width;
         let r_height = rule.rule_in().height;
         let r_width = rule.rule_in().width;
         if r_height > height || r_width > width || row_s >= height || col_s >= width {
-            return None;
+            return Ok(None);
         }
         if height - row_s < r_height || width - col_s < r_width {
-            return None;
+            return Ok(None);
         }
         let rule_in = rule.rule_in();
+        let rule_out = rule.rule_out();
+        let mut contradiction = false;
         for row in 0..r_height {
-            for col in 0..r_width {
-                #[expect(clippy::neg_cmp_op_on_partial_ord)]
-                if !(rule_in.cells[row][col] >= self.cells[row_s + row][col_s + col]) {
-                    return None;
-                }
+            let cells = &self.cells[row_s + row][col_s..col_s + r_width];
+            if !refines_all(&rule_in.cells[row], cells) {
+                return Ok(None);
             }
+            contradiction |= any_disjoint(&rule_out.cells[row], cells);
+            let colors = &self.colors[row_s + row][col_s..col_s + r_width];
+            if !refines_all(&rule_in.colors[row], colors) {
+                return Ok(None);
+            }
+            contradiction |= any_disjoint(&rule_out.colors[row], colors);
         }
         for row in 0..=r_height {
-            for col in 0..r_width {
-                #[expect(clippy::neg_cmp_op_on_partial_ord)]
-                if !(rule_in.hlinks[row][col] >= self.hlinks[row_s + row][col_s + col]) {
-                    return None;
-                }
+            let links = &self.hlinks[row_s + row][col_s..col_s + r_width];
+            if !refines_all(&rule_in.hlinks[row], links) {
+                return Ok(None);
             }
+            contradiction |= any_disjoint(&rule_out.hlinks[row], links);
         }
         for row in 0..r_height {
-            for col in 0..=r_width {
-                #[expect(clippy::neg_cmp_op_on_partial_ord)]
-                if !(rule_in.vlinks[row][col] >= self.vlinks[row_s + row][col_s + col]) {
-                    return None;
-                }
+            let links = &self.vlinks[row_s + row][col_s..=col_s + r_width];
+            if !refines_all(&rule_in.vlinks[row], links) {
+                return Ok(None);
             }
+            contradiction |= any_disjoint(&rule_out.vlinks[row], links);
         }
         for row in 0..2 * r_height {
-            for col in 0..2 * r_width {
-                #[expect(clippy::neg_cmp_op_on_partial_ord)]
-                if !(rule_in.corners[row][col] >= self.corners[2 * row_s + row][2 * col_s + col]) {
-                    return None;
-                }
+            let corners = &self.corners[2 * row_s + row][2 * col_s..2 * (col_s + r_width)];
+            if !refines_all(&rule_in.corners[row], corners) {
+                return Ok(None);
             }
+            contradiction |= any_disjoint(&rule_out.corners[row], corners);
+        }
+        if rule_in.has_wildcards && !rule_in.wildcards_match(self, row_s, col_s) {
+            return Ok(None);
+        }
+        let mut consequences = None;
+        if rule_in.has_labels || rule_out.has_labels {
+            let Some(mut relations) = self.labels_match(rule_in, row_s, col_s)? else {
+                return Ok(None);
+            };
+            consequences = Some(relations.consequences(rule_out)?);
+        }
+        if contradiction {
+            return Err(Contradiction);
         }
-        let rule_out = rule.rule_out();
         let mut modified = false;
         for row in 0..r_height {
             for col in 0..r_width {
-                let cell = &mut self.cells[row_s + row][col_s + col];
-                let new_cell = cell.gcd(&rule_out.cells[row][col]);
-                if *cell != new_cell {
-                    *cell = new_cell;
-                    modified = true;
-                }
+                let new_cell = self.cells[row_s + row][col_s + col].gcd(&rule_out.cells[row][col]);
+                modified |= self.write_cell(row_s + row, col_s + col, new_cell);
+                let new_color =
+                    self.colors[row_s + row][col_s + col].gcd(&rule_out.colors[row][col]);
+                modified |= self.write_color(row_s + row, col_s + col, new_color);
             }
         }
         for row in 0..=r_height {
             for col in 0..r_width {
-                let link = &mut self.hlinks[row_s + row][col_s + col];
-                let new_link = link.gcd(&rule_out.hlinks[row][col]);
-                if *link != new_link {
-                    *link = new_link;
-                    modified = true;
-                }
+                let new_link =
+                    self.hlinks[row_s + row][col_s + col].gcd(&rule_out.hlinks[row][col]);
+                modified |= self.write_hlink(row_s + row, col_s + col, new_link);
             }
         }
         for row in 0..r_height {
             for col in 0..=r_width {
-                let link = &mut self.vlinks[row_s + row][col_s + col];
-                let new_link = link.gcd(&rule_out.vlinks[row][col]);
-                if *link != new_link {
-                    *link = new_link;
-                    modified = true;
-                }
+                let new_link =
+                    self.vlinks[row_s + row][col_s + col].gcd(&rule_out.vlinks[row][col]);
+                modified |= self.write_vlink(row_s + row, col_s + col, new_link);
             }
         }
         for row in 0..2 * r_height {
             for col in 0..2 * r_width {
-                let corner = &mut self.corners[2 * row_s + row][2 * col_s + col];
-                let new_corner = corner.gcd(&rule_out.corners[row][col]);
-                if *corner != new_corner {
-                    *corner = new_corner;
-                    modified = true;
-                }
+                let new_corner =
+                    self.corners[2 * row_s + row][2 * col_s + col].gcd(&rule_out.corners[row][col]);
+                modified |= self.write_corner(2 * row_s + row, 2 * col_s + col, new_corner);
+            }
+        }
+        if let Some((links, corners)) = consequences {
+            for (edge, link) in links {
+                modified |= self.write_edge(edge, link);
             }
+            for (row, col, corner) in corners {
+                let new_corner = self.corners[row][col].gcd(&corner);
+                modified |= self.write_corner(row, col, new_corner);
+            }
+        }
+        if modified {
+            self.last_applied = Some((row_s, col_s, r_height, r_width));
         }
-        Some(modified)
+        Ok(Some(modified))
     }
 }
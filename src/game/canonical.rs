@@ -0,0 +1,192 @@
+use super::Rule;
+use super::Symmetry;
+use super::rule::{HalfRule, Label};
+
+/// 64-bit FNV-1a, which unlike the hashers of the standard library hashes
+/// the same way on every platform and version.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl HalfRule {
+    fn without_top_row(&self) -> HalfRule {
+        HalfRule {
+            height: self.height - 1,
+            cells: self.cells.without_top_rows(1),
+            colors: self.colors.without_top_rows(1),
+            hlinks: self.hlinks.without_top_rows(1),
+            vlinks: self.vlinks.without_top_rows(1),
+            corners: self.corners.without_top_rows(2),
+            cell_patterns: self.cell_patterns.without_top_rows(1),
+            hlink_patterns: self.hlink_patterns.without_top_rows(1),
+            vlink_patterns: self.vlink_patterns.without_top_rows(1),
+            outside: self.outside.without_top_rows(1),
+            hlink_labels: self.hlink_labels.without_top_rows(1),
+            vlink_labels: self.vlink_labels.without_top_rows(1),
+            ..self.clone()
+        }
+    }
+
+    /// The half with each label `old` replaced as given by `renames`, whose
+    /// entries `(old, new)` map the letter of `old` to `new` if `old` is not
+    /// opposite, and to its opposite otherwise.
+    fn relabelled(&self, renames: &[(Label, Label)]) -> HalfRule {
+        let rename = |label: &mut Option<Label>| {
+            if let Some(old) = *label {
+                let (first, new) = renames
+                    .iter()
+                    .find(|(first, _)| first.letter == old.letter)
+                    .unwrap();
+                *label = Some(Label {
+                    letter: new.letter,
+                    opposite: old.opposite ^ first.opposite,
+                });
+            }
+        };
+        let mut half = self.clone();
+        for row in 0..=half.height {
+            half.hlink_labels[row].iter_mut().for_each(rename);
+        }
+        for row in 0..half.height {
+            half.vlink_labels[row].iter_mut().for_each(rename);
+        }
+        half
+    }
+}
+
+impl Rule {
+    /// The rule as written in a rule file, without its name, as the text of
+    /// its two windows.
+    fn text(&self) -> Vec<u8> {
+        let mut text = Vec::new();
+        for half in [self.rule_in(), self.rule_out()] {
+            for row in 0..=4 * half.height {
+                half.full_render_row(&mut text, row).unwrap();
+                text.push(b'\n');
+            }
+            text.extend_from_slice(b"=>\n");
+        }
+        text
+    }
+
+    /// The rule with its labels renamed `a`, `b`... in order of appearance,
+    /// each first appearing in lower case.
+    fn with_labels_in_order(&self) -> Rule {
+        let mut renames: Vec<(Label, Label)> = Vec::new();
+        for half in [self.rule_in(), self.rule_out()] {
+            for (_, label) in half.labelled_links() {
+                if renames
+                    .iter()
+                    .all(|(first, _)| first.letter != label.letter)
+                {
+                    let new = Label {
+                        letter: b'a' + renames.len() as u8,
+                        opposite: false,
+                    };
+                    renames.push((label, new));
+                }
+            }
+        }
+        self.map_halves(|half| half.relabelled(&renames))
+    }
+
+    /// Canonical form of the rule used with `symmetry`, the same for rules
+    /// that differ only by margins, the names of their labels or an element of
+    /// `symmetry`. Border rows and columns that constrain nothing in either
+    /// half are trimmed, labels are renamed in order of appearance, and among
+    /// the images of the result under `symmetry`, the one whose text comes
+    /// first is kept.
+    pub fn canonicalize(&self, symmetry: Symmetry) -> Rule {
+        let mut rule = self.clone();
+        for _ in 0..4 {
+            while rule.rule_in().height > 1
+                && rule.rule_in().top_row_is_free()
+                && rule.rule_out().top_row_is_free()
+            {
+                rule = rule.map_halves(HalfRule::without_top_row);
+            }
+            rule = rule.rotated_90();
+        }
+        rule.orbit(symmetry)
+            .iter()
+            .map(|image| {
+                let image = image.with_labels_in_order();
+                (image.text(), image)
+            })
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+            .unwrap()
+            .1
+    }
+
+    /// Hash of the text of the canonical form of the rule used with
+    /// `symmetry`, which identifies the rule whatever its name, margins,
+    /// orientation and labels, and stays the same across platforms and
+    /// versions. Unless `symmetry` is the default [`Symmetry::All`], the text
+    /// starts with its `[SYMMETRY]` line, so that a rule used as written and
+    /// its mirror image, say, get different ids.
+    pub fn canonical_hash(&self, symmetry: Symmetry) -> u64 {
+        let mut text = Vec::new();
+        if symmetry != Symmetry::All {
+            text.extend_from_slice(format!("[SYMMETRY] {}\n", symmetry.name()).as_bytes());
+        }
+        text.extend(self.canonicalize(symmetry).text());
+        fnv1a(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A rule on a clue 1 linked on its left, and its mirror image.
+    fn left_and_right() -> (Rule, Rule) {
+        let left = Rule::new(
+            "Left",
+            "+.......+\n$       .\n$   1   .\n$       .\n+.......+\n=>\n\
+             +.......+\n$       .\n$   1   .\n$       .\n+       +",
+        )
+        .unwrap();
+        let right = Rule::new(
+            "Right",
+            "+.......+\n.       $\n.   1   $\n.       $\n+.......+\n=>\n\
+             +.......+\n.       $\n.   1   $\n.       $\n+       +",
+        )
+        .unwrap();
+        (left, right)
+    }
+
+    #[test]
+    fn mirror_images_share_an_id_only_under_reflections() {
+        let (left, right) = left_and_right();
+        assert_eq!(
+            left.canonical_hash(Symmetry::All),
+            right.canonical_hash(Symmetry::All)
+        );
+        for symmetry in [Symmetry::Rotations, Symmetry::Identity] {
+            assert_ne!(
+                left.canonical_hash(symmetry),
+                right.canonical_hash(symmetry)
+            );
+        }
+    }
+
+    #[test]
+    fn ids_depend_on_the_declared_symmetry() {
+        let (left, _) = left_and_right();
+        let turned = left.rotated_90().rotated_90();
+        assert_eq!(
+            left.canonical_hash(Symmetry::Rotations),
+            turned.canonical_hash(Symmetry::Rotations)
+        );
+        assert_ne!(
+            left.canonical_hash(Symmetry::All),
+            left.canonical_hash(Symmetry::Rotations)
+        );
+        assert_ne!(
+            left.canonical_hash(Symmetry::Rotations),
+            left.canonical_hash(Symmetry::Identity)
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use super::Game;
+use super::element::*;
+use super::vertex::Edge;
+use std::io::{self, Write};
+
+/// A formula in conjunctive normal form, with variables numbered from 1 and
+/// literals as signed variables, as in the DIMACS format.
+#[derive(Clone, Debug)]
+pub struct Cnf {
+    pub variables: usize,
+    pub clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    pub fn write_dimacs(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "p cnf {} {}", self.variables, self.clauses.len())?;
+        for clause in &self.clauses {
+            for literal in clause {
+                write!(out, "{literal} ")?;
+            }
+            writeln!(out, "0")?;
+        }
+        Ok(())
+    }
+}
+
+/// Clauses over `literals` that rule out every assignment whose number of
+/// true literals is not in `counts`.
+fn cardinality(literals: &[i32], counts: &[usize], clauses: &mut Vec<Vec<i32>>) {
+    for assignment in 0u32..1 << literals.len() {
+        if counts.contains(&(assignment.count_ones() as usize)) {
+            continue;
+        }
+        let clause = literals
+            .iter()
+            .enumerate()
+            .map(|(bit, &literal)| {
+                if assignment >> bit & 1 == 1 {
+                    -literal
+                } else {
+                    literal
+                }
+            })
+            .collect();
+        clauses.push(clause);
+    }
+}
+
+impl Game {
+    /// Links of the board that are not part of the padding, in the order of
+    /// their variables.
+    fn real_edges(&self) -> Vec<Edge> {
+        let hlinks =
+            (1..self.height).flat_map(|row| (1..self.width - 1).map(move |col| Edge::H(row, col)));
+        let vlinks =
+            (1..self.height - 1).flat_map(|row| (1..self.width).map(move |col| Edge::V(row, col)));
+        hlinks.chain(vlinks).collect()
+    }
+
+    /// Variable of a real link in [`Game::to_cnf`].
+    fn edge_variable(&self, edge: Edge) -> i32 {
+        let inner_width = self.width - 2;
+        let horizontal = (self.height - 1) * inner_width;
+        (match edge {
+            Edge::H(row, col) => (row - 1) * inner_width + col - 1,
+            Edge::V(row, col) => horizontal + (row - 1) * (inner_width + 1) + col - 1,
+        }) as i32
+            + 1
+    }
+
+    /// Variable of the link, or `None` for a link of the padding, which is
+    /// never linked.
+    fn link_literal(&self, edge: Edge) -> Option<i32> {
+        let real = match edge {
+            Edge::H(row, col) => row > 0 && row < self.height && col > 0 && col < self.width - 1,
+            Edge::V(row, col) => row > 0 && row < self.height - 1 && col > 0 && col < self.width,
+        };
+        real.then(|| self.edge_variable(edge))
+    }
+
+    /// Encodes the puzzle with one variable per link, true when linked: clues
+    /// and decided links are kept, every vertex has degree 0 or 2 and the loop
+    /// is not empty. That the loop is single is left to [`Game::loop_cuts`].
+    pub fn to_cnf(&self) -> Cnf {
+        let edges = self.real_edges();
+        let mut clauses = Vec::new();
+        for &edge in &edges {
+            let literal = self.edge_variable(edge);
+            match self.edge_link(edge) {
+                Link => clauses.push(vec![literal]),
+                Unlink => clauses.push(vec![-literal]),
+                LMaybe => (),
+            }
+        }
+        for row in 1..self.height - 1 {
+            for col in 1..self.width - 1 {
+                let count = match self.cells[row][col] {
+                    Empty => continue,
+                    Zero => 0,
+                    One => 1,
+                    Two => 2,
+                    Three => 3,
+                };
+                let literals = [
+                    Edge::H(row, col),
+                    Edge::H(row + 1, col),
+                    Edge::V(row, col),
+                    Edge::V(row, col + 1),
+                ]
+                .map(|edge| self.edge_variable(edge));
+                cardinality(&literals, &[count], &mut clauses);
+            }
+        }
+        for row in 0..=self.height {
+            for col in 0..=self.width {
+                let literals = self
+                    .vertex_edges(row, col)
+                    .filter_map(|(edge, _)| self.link_literal(edge))
+                    .collect::<Vec<_>>();
+                cardinality(&literals, &[0, 2], &mut clauses);
+            }
+        }
+        clauses.push(edges.iter().map(|&edge| self.edge_variable(edge)).collect());
+        Cnf {
+            variables: edges.len(),
+            clauses,
+        }
+    }
+
+    fn edge_link(&self, edge: Edge) -> LinkType {
+        match edge {
+            Edge::H(row, col) => self.hlinks[row][col],
+            Edge::V(row, col) => self.vlinks[row][col],
+        }
+    }
+
+    /// Decides every real link from a model of [`Game::to_cnf`], where
+    /// `model[i]` is the value of variable `i + 1`.
+    pub fn apply_model(&mut self, model: &[bool]) {
+        for edge in self.real_edges() {
+            let linked = model[self.edge_variable(edge) as usize - 1];
+            self.write_edge(edge, if linked { Link } else { Unlink });
+        }
+    }
+
+    /// Once every link is decided, clauses ruling out the loops of the board
+    /// if there are several: a loop cannot be linked together with a link of
+    /// another loop. Empty for a single loop.
+    pub fn loop_cuts(&self) -> Vec<Vec<i32>> {
+        let mut loops: Vec<Vec<Edge>> = Vec::new();
+        let mut seen = vec![vec![false; self.width + 1]; self.height + 1];
+        for row in 0..=self.height {
+            for col in 0..=self.width {
+                if seen[row][col] || self.vertex_degree(row, col).0 == 0 {
+                    continue;
+                }
+                let mut edges = Vec::new();
+                let mut stack = vec![(row, col)];
+                seen[row][col] = true;
+                while let Some((row, col)) = stack.pop() {
+                    for (edge, link) in self.vertex_edges(row, col) {
+                        if link != Link {
+                            continue;
+                        }
+                        let next = match edge {
+                            Edge::H(r, c) => (r, if c == col { c + 1 } else { c }),
+                            Edge::V(r, c) => (if r == row { r + 1 } else { r }, c),
+                        };
+                        if !edges.contains(&edge) {
+                            edges.push(edge);
+                        }
+                        if !seen[next.0][next.1] {
+                            seen[next.0][next.1] = true;
+                            stack.push(next);
+                        }
+                    }
+                }
+                loops.push(edges);
+            }
+        }
+        if loops.len() < 2 {
+            return Vec::new();
+        }
+        (0..loops.len())
+            .map(|index| {
+                let other = loops[(index + 1) % loops.len()][0];
+                loops[index]
+                    .iter()
+                    .chain([&other])
+                    .map(|&edge| -self.edge_variable(edge))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sat::SatSolver;
+
+    /// Links the four sides of the cell `(row, col)`.
+    fn draw_square(game: &mut Game, row: usize, col: usize) {
+        game.set_hlink(row, col, Link);
+        game.set_hlink(row + 1, col, Link);
+        game.set_vlink(row, col, Link);
+        game.set_vlink(row, col + 1, Link);
+    }
+
+    /// Negated variables of the sides of the cell `(row, col)`.
+    fn square_literals(game: &Game, row: usize, col: usize) -> Vec<i32> {
+        [
+            Edge::H(row, col),
+            Edge::H(row + 1, col),
+            Edge::V(row, col),
+            Edge::V(row, col + 1),
+        ]
+        .into_iter()
+        .map(|edge| -game.edge_variable(edge))
+        .collect()
+    }
+
+    #[test]
+    fn models_of_the_demo_puzzle_decode_to_its_solution() {
+        let game = Game::new(5, 5, "b2b2a1d222a202b333a");
+        let mut cnf = game.to_cnf();
+        loop {
+            let model = SatSolver::Builtin.solve(&cnf).unwrap().unwrap();
+            let mut solution = game.clone();
+            solution.apply_model(&model);
+            let cuts = solution.loop_cuts();
+            if cuts.is_empty() {
+                assert!(solution.is_solved());
+                break;
+            }
+            cnf.clauses.extend(cuts);
+        }
+    }
+
+    #[test]
+    fn a_single_loop_has_no_cuts() {
+        let mut game = Game::new(5, 5, "y");
+        draw_square(&mut game, 1, 1);
+        assert!(game.loop_cuts().is_empty());
+    }
+
+    #[test]
+    fn two_loops_are_cut_apart() {
+        let mut game = Game::new(5, 5, "y");
+        draw_square(&mut game, 1, 1);
+        draw_square(&mut game, 3, 3);
+        let cuts = game.loop_cuts();
+        assert_eq!(cuts.len(), 2);
+        for square in [square_literals(&game, 1, 1), square_literals(&game, 3, 3)] {
+            // A cut of a loop has its four links and one of the other loop.
+            assert!(cuts.iter().any(|cut| {
+                cut.len() == 5 && square.iter().all(|literal| cut.contains(literal))
+            }));
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use super::element::*;
+use super::journal::Change;
+use super::{Contradiction, Game};
+use std::collections::HashSet;
+use std::iter;
+
+/// Position `(row, col)` of a cell.
+type Cell = (usize, usize);
+
+/// Classes of cells whose colours are known up to a flip, kept in [`Game`]
+/// and grown by [`Game::constrain_colors`] as links are decided. Each cell
+/// knows whether its colour differs from its parent's, and the members of a
+/// class are linked in a cycle by `next`. The smaller class joins the larger
+/// and paths are never compressed, so that a join is undone by a split when
+/// the journal is rolled back.
+#[derive(Clone, Debug)]
+pub(super) struct ColorClasses {
+    parent: Vec<usize>,
+    differs: Vec<bool>,
+    size: Vec<usize>,
+    next: Vec<usize>,
+}
+
+impl ColorClasses {
+    pub(super) fn new(len: usize) -> ColorClasses {
+        ColorClasses {
+            parent: (0..len).collect(),
+            differs: vec![false; len],
+            size: vec![1; len],
+            next: (0..len).collect(),
+        }
+    }
+
+    /// Root of the class of `cell`, and whether their colours differ.
+    fn find(&self, cell: usize) -> (usize, bool) {
+        let (mut root, mut differs) = (cell, false);
+        while self.parent[root] != root {
+            differs ^= self.differs[root];
+            root = self.parent[root];
+        }
+        (root, differs)
+    }
+
+    /// Cells of the class of `cell`, starting with `cell`.
+    fn members(&self, cell: usize) -> Vec<usize> {
+        iter::successors(Some(cell), |&member| {
+            Some(self.next[member]).filter(|&next| next != cell)
+        })
+        .collect()
+    }
+
+    /// Makes the root `child` a child of the root `root`, whose colours differ
+    /// or not.
+    pub(super) fn join(&mut self, child: usize, root: usize, differs: bool) {
+        self.parent[child] = root;
+        self.differs[child] = differs;
+        self.size[root] += self.size[child];
+        self.next.swap(child, root);
+    }
+
+    /// Undoes the latest join, of `child` to `root`.
+    pub(super) fn split(&mut self, child: usize, root: usize) {
+        self.next.swap(child, root);
+        self.size[root] -= self.size[child];
+        self.parent[child] = child;
+        self.differs[child] = false;
+    }
+}
+
+/// Pending step of [`Game::constrain_colors`].
+enum Work {
+    /// Relate the colours of two neighbouring cells, if the link between
+    /// them is decided.
+    Relate(Cell, Cell),
+    /// Colour the class of a coloured cell.
+    Paint(Cell),
+    /// Decide the links around a cell from the classes and colours.
+    Decide(Cell),
+}
+
+impl Game {
+    fn cell_index(&self, (row, col): Cell) -> usize {
+        row * self.width + col
+    }
+
+    fn cell_at(&self, index: usize) -> Cell {
+        (index / self.width, index % self.width)
+    }
+
+    fn color_at(&self, index: usize) -> ColorType {
+        let (row, col) = self.cell_at(index);
+        self.colors[row][col]
+    }
+
+    /// Cells next to `(row, col)` on the board.
+    fn neighbours(&self, (row, col): Cell) -> impl Iterator<Item = Cell> + use<> {
+        let (height, width) = (self.height, self.width);
+        [
+            (row > 0).then(|| (row - 1, col)),
+            (row + 1 < height).then_some((row + 1, col)),
+            (col > 0).then(|| (row, col - 1)),
+            (col + 1 < width).then_some((row, col + 1)),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Link between the neighbouring cells `a` and `b`.
+    fn link_between(&self, a: Cell, b: Cell) -> LinkType {
+        if a.0 == b.0 {
+            self.vlinks[a.0][a.1.max(b.1)]
+        } else {
+            self.hlinks[a.0.max(b.0)][a.1]
+        }
+    }
+
+    fn write_link_between(&mut self, a: Cell, b: Cell, link: LinkType) -> bool {
+        if a.0 == b.0 {
+            self.write_vlink(a.0, a.1.max(b.1), link)
+        } else {
+            self.write_hlink(a.0.max(b.0), a.1, link)
+        }
+    }
+
+    /// Forgets the colour classes, which [`Game::constrain_colors`] then
+    /// rebuilds from the whole board. The splits are recorded like any
+    /// change, so they can be rolled back.
+    pub(super) fn forget_color_classes(&mut self) {
+        let splits: Vec<Change> = self
+            .journal
+            .iter()
+            .rev()
+            .filter_map(|&change| match change {
+                Change::Join(child, root, differs) => Some(Change::Split(child, root, differs)),
+                Change::Split(child, root, differs) => Some(Change::Join(child, root, differs)),
+                _ => None,
+            })
+            .collect();
+        for change in splits {
+            self.record(change);
+        }
+        self.colors_checked = None;
+    }
+
+    /// Colours cells inside or outside the loop. Cells across an unlinked
+    /// edge have the same colour and cells across a link opposite colours, so
+    /// decided links group the cells into classes whose colours are known up
+    /// to a flip. A class takes the colour of any of its coloured cells, and
+    /// the link between two cells is decided once they are in the same class
+    /// or both coloured. The classes are kept in the game, and only the
+    /// changes made since the previous call are examined. Returns whether the
+    /// board changed.
+    pub(super) fn constrain_colors(&mut self) -> Result<bool, Contradiction> {
+        let before = self.checkpoint();
+        let mut work = Vec::new();
+        if let Some(checked) = self.colors_checked {
+            for &change in &self.journal[checked..] {
+                match change {
+                    Change::HLink(row, col, ..) if 0 < row && row < self.height => {
+                        work.push(Work::Relate((row - 1, col), (row, col)));
+                    }
+                    Change::VLink(row, col, ..) if 0 < col && col < self.width => {
+                        work.push(Work::Relate((row, col - 1), (row, col)));
+                    }
+                    Change::Color(row, col, ..) => work.push(Work::Paint((row, col))),
+                    _ => {}
+                }
+            }
+        } else {
+            // Classes are all joined before any of them is painted.
+            for row in 0..self.height {
+                for col in 0..self.width {
+                    work.push(Work::Paint((row, col)));
+                }
+            }
+            for row in 0..self.height {
+                for col in 0..self.width {
+                    if row > 0 {
+                        work.push(Work::Relate((row - 1, col), (row, col)));
+                    }
+                    if col > 0 {
+                        work.push(Work::Relate((row, col - 1), (row, col)));
+                    }
+                }
+            }
+        }
+        // Roots of the classes painted by this call, which stay painted as
+        // later joins paint the classes they absorb.
+        let mut painted = HashSet::new();
+        while let Some(step) = work.pop() {
+            match step {
+                Work::Relate(a, b) => self.relate_cells(a, b, &mut work)?,
+                Work::Paint(cell) => self.paint_class(cell, &mut painted, &mut work)?,
+                Work::Decide(cell) => self.decide_links_around(cell, &mut work),
+            }
+        }
+        self.colors_checked = Some(self.journal.len());
+        Ok(self
+            .changes_since(before)
+            .iter()
+            .any(|change| !matches!(change, Change::Join(..) | Change::Split(..))))
+    }
+
+    /// Joins the classes of the neighbouring cells `a` and `b` if the link
+    /// between them is decided, and paints the new members of a coloured
+    /// class.
+    fn relate_cells(
+        &mut self,
+        a: Cell,
+        b: Cell,
+        work: &mut Vec<Work>,
+    ) -> Result<(), Contradiction> {
+        let link = self.link_between(a, b);
+        if link == LMaybe {
+            return Ok(());
+        }
+        let (root_a, differs_a) = self.color_classes.find(self.cell_index(a));
+        let (root_b, differs_b) = self.color_classes.find(self.cell_index(b));
+        // Whether the colours of the roots differ.
+        let differs = differs_a ^ differs_b ^ (link == Link);
+        if root_a == root_b {
+            return if differs { Err(Contradiction) } else { Ok(()) };
+        }
+        let (color_a, color_b) = (self.color_at(root_a), self.color_at(root_b));
+        if color_a != Unknown && color_b != Unknown && (color_a != color_b) != differs {
+            return Err(Contradiction);
+        }
+        let unpainted = match (color_a, color_b) {
+            (Unknown, Unknown) => None,
+            (Unknown, _) => Some((root_a, root_b)),
+            (_, Unknown) => Some((root_b, root_a)),
+            _ => None,
+        }
+        .map(|(unpainted, painted)| (self.color_classes.members(unpainted), painted));
+        let (child, root) = if self.color_classes.size[root_a] < self.color_classes.size[root_b] {
+            (root_a, root_b)
+        } else {
+            (root_b, root_a)
+        };
+        // Links that the join decides are between cells of the smaller class
+        // and cells of the larger one.
+        work.extend(
+            self.color_classes
+                .members(child)
+                .into_iter()
+                .map(|member| Work::Decide(self.cell_at(member))),
+        );
+        self.record(Change::Join(child, root, differs));
+        if let Some((members, painted)) = unpainted {
+            self.paint(painted, &members, work)?;
+        }
+        Ok(())
+    }
+
+    /// Colours the class of `cell`, if it is coloured and the class was not
+    /// yet painted by this call.
+    fn paint_class(
+        &mut self,
+        cell: Cell,
+        painted: &mut HashSet<usize>,
+        work: &mut Vec<Work>,
+    ) -> Result<(), Contradiction> {
+        let index = self.cell_index(cell);
+        if self.color_at(index) == Unknown {
+            return Ok(());
+        }
+        work.push(Work::Decide(cell));
+        if painted.insert(self.color_classes.find(index).0) {
+            let members = self.color_classes.members(index);
+            self.paint(index, &members, work)?;
+        }
+        Ok(())
+    }
+
+    /// Colours `members` of the class of the coloured cell `from`.
+    fn paint(
+        &mut self,
+        from: usize,
+        members: &[usize],
+        work: &mut Vec<Work>,
+    ) -> Result<(), Contradiction> {
+        let (_, from_differs) = self.color_classes.find(from);
+        let from_inside = self.color_at(from) == Inside;
+        for &member in members {
+            let (_, differs) = self.color_classes.find(member);
+            let color = if from_inside ^ from_differs ^ differs {
+                Inside
+            } else {
+                Outside
+            };
+            let (row, col) = self.cell_at(member);
+            match self.colors[row][col] {
+                Unknown => {
+                    self.write_color(row, col, color);
+                    work.push(Work::Decide((row, col)));
+                }
+                known if known != color => return Err(Contradiction),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Decides the undecided links between `cell` and the cells of its class
+    /// or, if it is coloured, the coloured cells next to it.
+    fn decide_links_around(&mut self, cell: Cell, work: &mut Vec<Work>) {
+        let (root, differs) = self.color_classes.find(self.cell_index(cell));
+        let color = self.colors[cell.0][cell.1];
+        for other in self.neighbours(cell) {
+            if self.link_between(cell, other) != LMaybe {
+                continue;
+            }
+            let (other_root, other_differs) = self.color_classes.find(self.cell_index(other));
+            let other_color = self.colors[other.0][other.1];
+            let linked = if root == other_root {
+                differs != other_differs
+            } else if color != Unknown && other_color != Unknown {
+                color != other_color
+            } else {
+                continue;
+            };
+            self.write_link_between(cell, other, if linked { Link } else { Unlink });
+            work.push(Work::Relate(cell, other));
+        }
+    }
+}
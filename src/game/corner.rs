@@ -0,0 +1,93 @@
+use super::element::*;
+use super::{Contradiction, Game};
+
+// Sets of link counts are bitmasks with bit `n` standing for `n` links,
+// which is also how corner masks are laid out.
+
+fn link_counts(link: LinkType) -> u8 {
+    match link {
+        LMaybe => 0b11,
+        Link => 0b10,
+        Unlink => 0b01,
+    }
+}
+
+fn link_of_counts(counts: u8) -> Result<LinkType, Contradiction> {
+    match counts {
+        0b11 => Ok(LMaybe),
+        0b10 => Ok(Link),
+        0b01 => Ok(Unlink),
+        _ => Err(Contradiction),
+    }
+}
+
+fn corner_of_counts(counts: u8) -> Result<CornerType, Contradiction> {
+    match counts & 0b111 {
+        0 => Err(Contradiction),
+        counts => Ok(CornerType::from_mask(counts)),
+    }
+}
+
+fn clue_counts(cell: CellType) -> u8 {
+    match cell {
+        Empty => 0b11111,
+        Zero => 0b1,
+        One => 0b10,
+        Two => 0b100,
+        Three => 0b1000,
+    }
+}
+
+/// Counts `a + b` with `a` in `a_counts` and `b` in `b_counts`.
+fn sums(a_counts: u8, b_counts: u8) -> u8 {
+    (0..8)
+        .filter(|&b| b_counts >> b & 1 == 1)
+        .fold(0, |counts, b| counts | a_counts << b)
+}
+
+/// Counts `s - b` with `s` in `sum_counts` and `b` in `b_counts`.
+fn differences(sum_counts: u8, b_counts: u8) -> u8 {
+    (0..8)
+        .filter(|&b| b_counts >> b & 1 == 1)
+        .fold(0, |counts, b| counts | sum_counts >> b)
+}
+
+impl Game {
+    /// Narrows the corners of the cell at `(row, col)` and the links around it
+    /// so that they agree with each other: each corner counts the links of its
+    /// two edges, and the clue is the sum of either pair of diagonally
+    /// opposite corners. Returns whether the board changed.
+    pub(super) fn constrain_cell(&mut self, row: usize, col: usize) -> Result<bool, Contradiction> {
+        let mut modified = false;
+        loop {
+            let mut changed = false;
+            let mut corners = [[0; 2]; 2];
+            for (dr, dc) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                let corner_pos = (2 * row + dr, 2 * col + dc);
+                let corner = self.corners[corner_pos.0][corner_pos.1].to_mask();
+                let hlink = link_counts(self.hlinks[row + dr][col]);
+                let vlink = link_counts(self.vlinks[row][col + dc]);
+                let corner = corner_of_counts(corner & sums(hlink, vlink))?;
+                changed |= self.write_corner(corner_pos.0, corner_pos.1, corner);
+                let corner = corner.to_mask();
+                let hlink = link_of_counts(hlink & differences(corner, vlink))?;
+                changed |= self.write_hlink(row + dr, col, hlink);
+                let vlink = link_of_counts(vlink & differences(corner, link_counts(hlink)))?;
+                changed |= self.write_vlink(row, col + dc, vlink);
+                corners[dr][dc] = corner;
+            }
+            let total = clue_counts(self.cells[row][col])
+                & sums(corners[0][0], corners[1][1])
+                & sums(corners[0][1], corners[1][0]);
+            for (dr, dc) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                let opposite = corners[1 - dr][1 - dc];
+                let corner = corner_of_counts(corners[dr][dc] & differences(total, opposite))?;
+                changed |= self.write_corner(2 * row + dr, 2 * col + dc, corner);
+            }
+            if !changed {
+                return Ok(modified);
+            }
+            modified = true;
+        }
+    }
+}
@@ -1,36 +1,100 @@
+use super::grid::Grid;
 use std::cmp::Ordering;
+use std::io::{self, Write};
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+/// Board elements are stored as their masks, see [`Mask`].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
 pub enum CellType {
-    Empty,
-    Zero,
-    One,
-    Two,
-    Three,
+    Empty = 15,
+    Zero = 1,
+    One = 2,
+    Two = 4,
+    Three = 8,
 }
 pub use CellType::*;
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
 pub enum LinkType {
-    LMaybe,
-    Link,
-    Unlink,
+    LMaybe = 3,
+    Link = 1,
+    Unlink = 2,
 }
 pub use LinkType::*;
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
 pub enum CornerType {
-    CMaybe,
-    CZero,
-    COne,
-    CTwo,
-    Even,
-    Less,
-    Greater,
+    CMaybe = 7,
+    CZero = 1,
+    COne = 2,
+    CTwo = 4,
+    Even = 5,
+    Less = 3,
+    Greater = 6,
 }
 pub use CornerType::*;
 
-trait Mask {
+/// Whether the loop visits a vertex, giving it degree 2, or not, giving it
+/// degree 0.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
+pub enum VertexType {
+    VMaybe = 3,
+    Unvisited = 1,
+    Visited = 2,
+}
+pub use VertexType::*;
+
+/// Whether a cell lies inside or outside the loop.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
+pub enum ColorType {
+    Unknown = 3,
+    Inside = 1,
+    Outside = 2,
+}
+pub use ColorType::*;
+
+impl CellType {
+    pub fn to_char(self) -> char {
+        match self {
+            Empty => ' ',
+            Zero => '0',
+            One => '1',
+            Two => '2',
+            Three => '3',
+        }
+    }
+}
+
+impl ColorType {
+    pub fn to_char(self) -> char {
+        match self {
+            Unknown => ' ',
+            Inside => 'I',
+            Outside => 'O',
+        }
+    }
+}
+
+impl CornerType {
+    pub fn to_char(self) -> char {
+        match self {
+            CMaybe => ' ',
+            CZero => '0',
+            COne => '1',
+            CTwo => '2',
+            Even => 'E',
+            Less => 'L',
+            Greater => 'G',
+        }
+    }
+}
+
+/// Set of the values an element can still take, one bit per value.
+pub(super) trait Mask {
     fn to_mask(&self) -> u8;
     fn from_mask(mask: u8) -> Self;
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
@@ -51,13 +115,7 @@ trait Mask {
 
 impl Mask for CellType {
     fn to_mask(&self) -> u8 {
-        match self {
-            Empty => 15,
-            Zero => 1,
-            One => 2,
-            Two => 4,
-            Three => 8,
-        }
+        *self as u8
     }
 
     fn from_mask(mask: u8) -> Self {
@@ -74,11 +132,7 @@ impl Mask for CellType {
 
 impl Mask for LinkType {
     fn to_mask(&self) -> u8 {
-        match self {
-            LMaybe => 3,
-            Link => 1,
-            Unlink => 2,
-        }
+        *self as u8
     }
 
     fn from_mask(mask: u8) -> Self {
@@ -91,18 +145,25 @@ impl Mask for LinkType {
     }
 }
 
-impl Mask for CornerType {
+impl Mask for ColorType {
     fn to_mask(&self) -> u8 {
-        match self {
-            CMaybe => 7,
-            CZero => 1,
-            COne => 2,
-            CTwo => 4,
-            Even => 5,
-            Less => 3,
-            Greater => 6,
+        *self as u8
+    }
+
+    fn from_mask(mask: u8) -> Self {
+        match mask {
+            3 => Unknown,
+            1 => Inside,
+            2 => Outside,
+            _ => panic!(),
         }
     }
+}
+
+impl Mask for CornerType {
+    fn to_mask(&self) -> u8 {
+        *self as u8
+    }
 
     fn from_mask(mask: u8) -> Self {
         match mask {
@@ -118,12 +179,33 @@ impl Mask for CornerType {
     }
 }
 
+impl Mask for VertexType {
+    fn to_mask(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_mask(mask: u8) -> Self {
+        match mask {
+            3 => VMaybe,
+            1 => Unvisited,
+            2 => Visited,
+            _ => panic!(),
+        }
+    }
+}
+
 impl PartialOrd for CellType {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Mask::partial_cmp(self, other)
     }
 }
 
+impl PartialOrd for ColorType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Mask::partial_cmp(self, other)
+    }
+}
+
 impl PartialOrd for LinkType {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Mask::partial_cmp(self, other)
@@ -146,100 +228,129 @@ impl<T: Mask> Gcd for T {
     }
 }
 
+/// Set of board values matched by an element of a rule, with one bit per
+/// mask: an element matches if the bit of its mask is set. Exact glyphs match
+/// every refinement of their value, wildcards an arbitrary set of values.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub(super) struct Pattern(u16);
+
+impl Pattern {
+    pub(super) fn of<T: Mask>(values: &[T]) -> Pattern {
+        Pattern(
+            values
+                .iter()
+                .fold(0, |bits, value| bits | 1 << value.to_mask()),
+        )
+    }
+
+    pub(super) fn refining<T: Mask>(value: T) -> Pattern {
+        let mask = value.to_mask();
+        Pattern(
+            (1..16)
+                .filter(|&other| other & !mask == 0)
+                .fold(0, |bits, other| bits | 1 << other),
+        )
+    }
+
+    pub(super) fn matches<T: Mask>(self, value: T) -> bool {
+        self.0 >> value.to_mask() & 1 == 1
+    }
+}
+
+/// Whether every value is a refinement of the pattern at the same position.
+pub(super) fn refines_all<T: Mask>(patterns: &[T], values: &[T]) -> bool {
+    patterns
+        .iter()
+        .zip(values)
+        .all(|(pattern, value)| value.to_mask() & !pattern.to_mask() == 0)
+}
+
+/// Whether some value has nothing in common with the pattern at the same
+/// position.
+pub(super) fn any_disjoint<T: Mask>(patterns: &[T], values: &[T]) -> bool {
+    patterns
+        .iter()
+        .zip(values)
+        .any(|(pattern, value)| value.to_mask() & pattern.to_mask() == 0)
+}
+
 #[expect(clippy::too_many_arguments)]
-pub fn full_print_row(
+pub fn full_render_row<W: Write>(
+    out: &mut W,
     height: usize,
     width: usize,
-    cells: &[Vec<CellType>],
-    hlinks: &[Vec<LinkType>],
-    vlinks: &[Vec<LinkType>],
-    corners: &[Vec<CornerType>],
+    cells: &Grid<CellType>,
+    colors: &Grid<ColorType>,
+    hlinks: &Grid<LinkType>,
+    vlinks: &Grid<LinkType>,
+    corners: &Grid<CornerType>,
     row: usize,
     trim_left_and_right: bool,
-) {
+) -> io::Result<()> {
     assert!(row <= 4 * height);
     let col_l = if trim_left_and_right { 1 } else { 0 };
     let col_r = width - if trim_left_and_right { 1 } else { 0 };
-    if row % 4 == 0 {
+    if row.is_multiple_of(4) {
         let row = row / 4;
-        print!("+");
+        write!(out, "+")?;
         for col in col_l..col_r {
-            print!(
+            write!(
+                out,
                 "{}+",
                 match hlinks[row][col] {
                     LMaybe => ".......",
                     Link => "=======",
                     Unlink => "       ",
                 }
-            );
+            )?;
         }
-        return;
+        return Ok(());
     }
+    let render_vlink = |out: &mut W, row: usize, col: usize| {
+        write!(
+            out,
+            "{}",
+            match vlinks[row][col] {
+                LMaybe => ".",
+                Link => "$",
+                Unlink => " ",
+            }
+        )
+    };
     if row % 4 == 2 {
         let row = row / 4;
-        let print_vlink = |col: usize| {
-            print!(
-                "{}",
-                match vlinks[row][col] {
-                    LMaybe => ".",
-                    Link => "$",
-                    Unlink => " ",
-                }
-            );
-        };
         let mut first = true;
         for col in col_l..col_r {
             if first {
-                print_vlink(col);
+                render_vlink(out, row, col)?;
                 first = false;
             }
-            print!(
-                "   {}   ",
-                match cells[row][col] {
-                    Empty => ' ',
-                    Zero => '0',
-                    One => '1',
-                    Two => '2',
-                    Three => '3',
-                }
-            );
-            print_vlink(col + 1);
+            write!(
+                out,
+                " {} {}   ",
+                colors[row][col].to_char(),
+                cells[row][col].to_char()
+            )?;
+            render_vlink(out, row, col + 1)?;
         }
-        return;
+        return Ok(());
     }
     let row = row / 2;
     let parity = row % 2;
     let row = row / 2;
-    let print_vlink = |col: usize| {
-        print!(
-            "{}",
-            match vlinks[row][col] {
-                LMaybe => ".",
-                Link => "$",
-                Unlink => " ",
-            }
-        );
-    };
     let mut first = true;
     for col in col_l..col_r {
         if first {
-            print_vlink(col);
+            render_vlink(out, row, col)?;
             first = false;
         }
-        let to_char = |corner: CornerType| match corner {
-            CMaybe => ' ',
-            CZero => '0',
-            COne => '1',
-            CTwo => '2',
-            Even => 'E',
-            Less => 'L',
-            Greater => 'G',
-        };
-        print!(
+        write!(
+            out,
             " {}   {} ",
-            to_char(corners[2 * row + parity][2 * col]),
-            to_char(corners[2 * row + parity][2 * col + 1])
-        );
-        print_vlink(col + 1);
+            corners[2 * row + parity][2 * col].to_char(),
+            corners[2 * row + parity][2 * col + 1].to_char()
+        )?;
+        render_vlink(out, row, col + 1)?;
     }
+    Ok(())
 }
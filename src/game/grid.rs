@@ -0,0 +1,113 @@
+use std::ops::{Index, IndexMut};
+
+/// Row-major grid stored in a single vector, indexed as `grid[row][col]`.
+/// Board elements are `#[repr(u8)]` with their masks as discriminants, so a
+/// grid of them is a flat array of mask bytes and a row is a contiguous slice.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Grid<T> {
+    height: usize,
+    width: usize,
+    elements: Vec<T>,
+}
+
+impl<T: Copy> Grid<T> {
+    pub fn new(height: usize, width: usize, value: T) -> Grid<T> {
+        Grid {
+            height,
+            width,
+            elements: vec![value; height * width],
+        }
+    }
+
+    /// The grid rotated by a quarter turn, `rotated[row][col]` being
+    /// `self[col][height - 1 - row]` where `height` is the rotated height.
+    pub fn rotated_90(&self) -> Grid<T> {
+        let (height, width) = (self.width, self.height);
+        Grid {
+            height,
+            width,
+            elements: (0..height)
+                .flat_map(|row| (0..width).map(move |col| self[col][height - 1 - row]))
+                .collect(),
+        }
+    }
+
+    /// The grid without its first `count` rows.
+    pub fn without_top_rows(&self, count: usize) -> Grid<T> {
+        Grid {
+            height: self.height - count,
+            width: self.width,
+            elements: self.elements[count * self.width..].to_vec(),
+        }
+    }
+
+    pub fn reversed_lr(&self) -> Grid<T> {
+        Grid {
+            height: self.height,
+            width: self.width,
+            elements: self
+                .rows()
+                .flat_map(|row| row.iter().copied().rev())
+                .collect(),
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    /// The rows of the grid, which may be empty.
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).map(|row| &self[row])
+    }
+}
+
+impl<T> From<Vec<Vec<T>>> for Grid<T> {
+    fn from(rows: Vec<Vec<T>>) -> Grid<T> {
+        let width = rows.first().map_or(0, Vec::len);
+        assert!(rows.iter().all(|row| row.len() == width));
+        Grid {
+            height: rows.len(),
+            width,
+            elements: rows.into_iter().flatten().collect(),
+        }
+    }
+}
+
+impl<T> Index<usize> for Grid<T> {
+    type Output = [T];
+
+    fn index(&self, row: usize) -> &[T] {
+        &self.elements[row * self.width..(row + 1) * self.width]
+    }
+}
+
+impl<T> IndexMut<usize> for Grid<T> {
+    fn index_mut(&mut self, row: usize) -> &mut [T] {
+        &mut self.elements[row * self.width..(row + 1) * self.width]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_grids_have_rows_of_their_width() {
+        let grid = Grid::new(3, 0, 0u8);
+        assert_eq!(grid.rows().count(), 3);
+        assert!(grid.rows().all(<[u8]>::is_empty));
+        let rotated = grid.rotated_90();
+        assert_eq!(rotated.rows().count(), 0);
+        assert_eq!(rotated.rotated_90(), grid.reversed_lr());
+        assert_eq!(Grid::new(0, 2, 0u8).rows().count(), 0);
+    }
+
+    #[test]
+    fn rotated_90_moves_rows_to_columns() {
+        let grid = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        assert_eq!(
+            grid.rotated_90(),
+            Grid::from(vec![vec![3, 6], vec![2, 5], vec![1, 4]])
+        );
+        assert_eq!(grid.without_top_rows(1), Grid::from(vec![vec![4, 5, 6]]));
+    }
+}
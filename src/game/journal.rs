@@ -0,0 +1,360 @@
+use super::Game;
+use super::element::*;
+
+/// A single element of the board changing from one value to another, or two
+/// colour classes joining or splitting.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub(super) enum Change {
+    Cell(usize, usize, CellType, CellType),
+    Color(usize, usize, ColorType, ColorType),
+    HLink(usize, usize, LinkType, LinkType),
+    VLink(usize, usize, LinkType, LinkType),
+    Corner(usize, usize, CornerType, CornerType),
+    Vertex(usize, usize, VertexType, VertexType),
+    /// See [`ColorClasses::join`](super::colors::ColorClasses::join).
+    Join(usize, usize, bool),
+    /// Undoes the [`Change::Join`] with the same fields.
+    Split(usize, usize, bool),
+}
+
+/// A position in the journal of a [`Game`], to roll back to.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Checkpoint(usize);
+
+impl Game {
+    fn apply_change(&mut self, change: Change, forward: bool) {
+        fn pick<T>(forward: bool, old: T, new: T) -> T {
+            if forward { new } else { old }
+        }
+        match change {
+            Change::Cell(row, col, old, new) => self.cells[row][col] = pick(forward, old, new),
+            Change::Color(row, col, old, new) => self.colors[row][col] = pick(forward, old, new),
+            Change::HLink(row, col, old, new) => self.hlinks[row][col] = pick(forward, old, new),
+            Change::VLink(row, col, old, new) => self.vlinks[row][col] = pick(forward, old, new),
+            Change::Corner(row, col, old, new) => self.corners[row][col] = pick(forward, old, new),
+            Change::Vertex(row, col, old, new) => self.vertices[row][col] = pick(forward, old, new),
+            Change::Join(child, root, differs) | Change::Split(child, root, differs) => {
+                if forward == matches!(change, Change::Join(..)) {
+                    self.color_classes.join(child, root, differs);
+                } else {
+                    self.color_classes.split(child, root);
+                }
+            }
+        }
+    }
+
+    pub(super) fn record(&mut self, change: Change) {
+        self.apply_change(change, true);
+        self.journal.push(change);
+        self.edits.push(false);
+        self.redo_stack.clear();
+    }
+
+    /// Marks the last change as an edit, and if `widened`, forgets what was
+    /// deduced before it.
+    pub(super) fn mark_edit(&mut self, widened: bool) {
+        *self.edits.last_mut().unwrap() = true;
+        if widened {
+            self.forget_deductions();
+        }
+    }
+
+    /// Sets every element back to its value before any change, except for the
+    /// edits, which keep their latest values, and forgets the colour classes.
+    /// The resets are recorded like any change, so they can be rolled back.
+    fn forget_deductions(&mut self) {
+        self.forget_color_classes();
+        let mut baseline = self.clone();
+        for &change in self.journal.iter().rev() {
+            baseline.apply_change(change, false);
+        }
+        for (&change, &edit) in self.journal.iter().zip(&self.edits) {
+            if edit {
+                baseline.apply_change(change, true);
+            }
+        }
+        for row in 0..self.height {
+            for col in 0..self.width {
+                self.write_color(row, col, baseline.colors[row][col]);
+            }
+        }
+        for row in 0..=self.height {
+            for col in 0..self.width {
+                self.write_hlink(row, col, baseline.hlinks[row][col]);
+            }
+        }
+        for row in 0..self.height {
+            for col in 0..=self.width {
+                self.write_vlink(row, col, baseline.vlinks[row][col]);
+            }
+        }
+        for row in 0..2 * self.height {
+            for col in 0..2 * self.width {
+                self.write_corner(row, col, baseline.corners[row][col]);
+            }
+        }
+        for row in 0..=self.height {
+            for col in 0..=self.width {
+                self.write_vertex(row, col, baseline.vertices[row][col]);
+            }
+        }
+    }
+
+    /// Sets a cell, recording the change in the journal. Returns whether the
+    /// value changed.
+    pub(super) fn write_cell(&mut self, row: usize, col: usize, cell: CellType) -> bool {
+        let old = self.cells[row][col];
+        if old == cell {
+            return false;
+        }
+        self.record(Change::Cell(row, col, old, cell));
+        true
+    }
+
+    pub(super) fn write_color(&mut self, row: usize, col: usize, color: ColorType) -> bool {
+        let old = self.colors[row][col];
+        if old == color {
+            return false;
+        }
+        self.record(Change::Color(row, col, old, color));
+        true
+    }
+
+    pub(super) fn write_hlink(&mut self, row: usize, col: usize, link: LinkType) -> bool {
+        let old = self.hlinks[row][col];
+        if old == link {
+            return false;
+        }
+        self.record(Change::HLink(row, col, old, link));
+        true
+    }
+
+    pub(super) fn write_vlink(&mut self, row: usize, col: usize, link: LinkType) -> bool {
+        let old = self.vlinks[row][col];
+        if old == link {
+            return false;
+        }
+        self.record(Change::VLink(row, col, old, link));
+        true
+    }
+
+    pub(super) fn write_corner(&mut self, row: usize, col: usize, corner: CornerType) -> bool {
+        let old = self.corners[row][col];
+        if old == corner {
+            return false;
+        }
+        self.record(Change::Corner(row, col, old, corner));
+        true
+    }
+
+    pub(super) fn write_vertex(&mut self, row: usize, col: usize, vertex: VertexType) -> bool {
+        let old = self.vertices[row][col];
+        if old == vertex {
+            return false;
+        }
+        self.record(Change::Vertex(row, col, old, vertex));
+        true
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.journal.len())
+    }
+
+    /// Changes made since `checkpoint`, oldest first.
+    pub(super) fn changes_since(&self, checkpoint: Checkpoint) -> &[Change] {
+        &self.journal[checkpoint.0..]
+    }
+
+    /// Undoes every change made since `checkpoint`. The undone changes can be
+    /// reapplied with [`Game::redo`] until the board is modified again.
+    ///
+    /// Panics if `checkpoint` is beyond the journal, as happens when it was
+    /// taken after an earlier checkpoint that was rolled back to.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        assert!(
+            checkpoint.0 <= self.journal.len(),
+            "checkpoint {} is beyond the journal of {} changes",
+            checkpoint.0,
+            self.journal.len()
+        );
+        let undone = self.journal.split_off(checkpoint.0);
+        let edits = self.edits.split_off(checkpoint.0);
+        self.colors_checked = self.colors_checked.map(|checked| checked.min(checkpoint.0));
+        for &change in undone.iter().rev() {
+            self.apply_change(change, false);
+        }
+        if !undone.is_empty() {
+            self.last_applied = None;
+            self.redo_stack.push((undone, edits));
+        }
+    }
+
+    /// Reapplies the most recently rolled back changes. Returns `false` if
+    /// there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some((changes, edits)) = self.redo_stack.pop() else {
+            return false;
+        };
+        for &change in &changes {
+            self.apply_change(change, true);
+        }
+        self.journal.extend(changes);
+        self.edits.extend(edits);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game() -> Game {
+        Game::new(5, 5, "y")
+    }
+
+    #[test]
+    fn rollback_undoes_changes_since_checkpoint() {
+        let mut game = game();
+        game.set_hlink(1, 1, Link);
+        let checkpoint = game.checkpoint();
+        game.set_hlink(1, 2, Link);
+        game.set_vlink(1, 1, Unlink);
+        game.rollback(checkpoint);
+        assert_eq!(game.hlink(1, 1), Link);
+        assert_eq!(game.hlink(1, 2), LMaybe);
+        assert_eq!(game.vlink(1, 1), LMaybe);
+        assert_eq!(game.checkpoint(), checkpoint);
+    }
+
+    #[test]
+    fn redo_reapplies_rolled_back_changes() {
+        let mut game = game();
+        let checkpoint = game.checkpoint();
+        game.set_hlink(1, 2, Link);
+        game.set_vlink(1, 1, Unlink);
+        let after = game.checkpoint();
+        game.rollback(checkpoint);
+        assert!(game.redo());
+        assert_eq!(game.hlink(1, 2), Link);
+        assert_eq!(game.vlink(1, 1), Unlink);
+        assert_eq!(game.checkpoint(), after);
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn redo_undoes_rollbacks_in_reverse_order() {
+        let mut game = game();
+        let first = game.checkpoint();
+        game.set_hlink(1, 2, Link);
+        let second = game.checkpoint();
+        game.set_hlink(1, 3, Link);
+        game.rollback(second);
+        game.rollback(first);
+        assert!(game.redo());
+        assert_eq!((game.hlink(1, 2), game.hlink(1, 3)), (Link, LMaybe));
+        assert!(game.redo());
+        assert_eq!((game.hlink(1, 2), game.hlink(1, 3)), (Link, Link));
+    }
+
+    #[test]
+    fn new_write_clears_redo() {
+        let mut game = game();
+        let checkpoint = game.checkpoint();
+        game.set_hlink(1, 2, Link);
+        game.rollback(checkpoint);
+        game.set_vlink(1, 1, Unlink);
+        assert!(!game.redo());
+        assert_eq!(game.hlink(1, 2), LMaybe);
+        assert_eq!(game.vlink(1, 1), Unlink);
+    }
+
+    #[test]
+    fn unchanged_write_keeps_redo() {
+        let mut game = game();
+        let checkpoint = game.checkpoint();
+        game.set_hlink(1, 2, Link);
+        game.rollback(checkpoint);
+        game.set_hlink(1, 3, LMaybe);
+        assert!(game.redo());
+    }
+
+    #[test]
+    fn clearing_a_link_forgets_deductions() {
+        let mut game = game();
+        game.set_hlink(1, 1, Link);
+        game.propagate(&[]).unwrap();
+        assert_eq!(game.vlink(1, 1), Link);
+        let checkpoint = game.checkpoint();
+        game.set_hlink(1, 1, Unlink);
+        game.set_hlink(1, 1, LMaybe);
+        assert_eq!(game.vlink(1, 1), LMaybe);
+        assert!(game.vertices.rows().flatten().all(|&v| v != Visited));
+        assert!(
+            game.corners
+                .rows()
+                .flatten()
+                .all(|&c| c == CMaybe || c == CZero)
+        );
+        game.propagate(&[]).unwrap();
+        assert_eq!(game.hlink(1, 1), LMaybe);
+        game.rollback(checkpoint);
+        assert_eq!((game.hlink(1, 1), game.vlink(1, 1)), (Link, Link));
+    }
+
+    #[test]
+    fn clearing_keeps_other_edits() {
+        let mut game = game();
+        game.set_vlink(3, 3, Unlink);
+        game.set_hlink(1, 1, Link);
+        game.propagate(&[]).unwrap();
+        game.set_hlink(1, 1, LMaybe);
+        assert_eq!(game.vlink(3, 3), Unlink);
+        assert_eq!(game.vlink(1, 1), LMaybe);
+    }
+
+    #[test]
+    fn narrowing_edits_keep_deductions() {
+        let mut game = game();
+        game.set_hlink(1, 1, Link);
+        game.propagate(&[]).unwrap();
+        game.set_hlink(3, 3, Unlink);
+        assert_eq!(game.vlink(1, 1), Link);
+    }
+
+    #[test]
+    fn rollback_splits_color_classes() {
+        let mut game = game();
+        game.constrain_colors().unwrap();
+        let checkpoint = game.checkpoint();
+        game.set_vlink(1, 1, Unlink);
+        game.constrain_colors().unwrap();
+        assert_eq!(game.colors[1][1], Outside);
+        game.rollback(checkpoint);
+        game.set_vlink(1, 1, Link);
+        assert_eq!(game.constrain_colors(), Ok(true));
+        assert_eq!(game.colors[1][1], Inside);
+    }
+
+    #[test]
+    fn clearing_a_link_forgets_color_classes() {
+        let mut game = game();
+        game.set_vlink(1, 1, Unlink);
+        game.constrain_colors().unwrap();
+        game.set_vlink(1, 1, LMaybe);
+        assert_eq!(game.colors[1][1], Unknown);
+        game.set_vlink(1, 1, Link);
+        assert_eq!(game.constrain_colors(), Ok(true));
+        assert_eq!(game.colors[1][1], Inside);
+    }
+
+    #[test]
+    #[should_panic(expected = "beyond the journal")]
+    fn stale_checkpoint_panics() {
+        let mut game = game();
+        let checkpoint = game.checkpoint();
+        game.set_hlink(1, 2, Link);
+        let stale = game.checkpoint();
+        game.rollback(checkpoint);
+        game.rollback(stale);
+    }
+}
@@ -0,0 +1,300 @@
+use super::element::*;
+use super::rule::HalfRule;
+use super::vertex::Edge;
+use super::{Contradiction, Game};
+
+/// Union-find over the nodes of [`Relations`]: a node for each link of the
+/// window, and [`UNLINKED`]. Each node also knows whether its value differs
+/// from its parent's, so that links in the class of `UNLINKED` are decided,
+/// linked when they differ from it.
+struct Classes {
+    parent: Vec<usize>,
+    differs: Vec<bool>,
+}
+
+impl Classes {
+    fn new(len: usize) -> Classes {
+        Classes {
+            parent: (0..len).collect(),
+            differs: vec![false; len],
+        }
+    }
+
+    /// Root of the class of `node`, and whether their values differ.
+    fn find(&mut self, node: usize) -> (usize, bool) {
+        let (mut root, mut differs) = (node, false);
+        while self.parent[root] != root {
+            differs ^= self.differs[root];
+            root = self.parent[root];
+        }
+        let (mut node, mut node_differs) = (node, differs);
+        while node != root {
+            let (parent, parent_differs) = (self.parent[node], node_differs ^ self.differs[node]);
+            self.parent[node] = root;
+            self.differs[node] = node_differs;
+            (node, node_differs) = (parent, parent_differs);
+        }
+        (root, differs)
+    }
+
+    /// Records that the values of `a` and `b` differ or not.
+    fn union(&mut self, a: usize, b: usize, differ: bool) -> Result<(), Contradiction> {
+        let (root_a, differs_a) = self.find(a);
+        let (root_b, differs_b) = self.find(b);
+        if root_a == root_b {
+            return if differs_a ^ differs_b == differ {
+                Ok(())
+            } else {
+                Err(Contradiction)
+            };
+        }
+        self.parent[root_a] = root_b;
+        self.differs[root_a] = differs_a ^ differs_b ^ differ;
+        Ok(())
+    }
+}
+
+/// Known relations between the links of a rule window anchored at
+/// `(row_s, col_s)`: two links in the same class are equal or opposite, and
+/// links in the class of [`UNLINKED`] are decided.
+pub(super) struct Relations {
+    classes: Classes,
+    height: usize,
+    width: usize,
+    row_s: usize,
+    col_s: usize,
+}
+
+/// Node standing for the value `Unlink`.
+const UNLINKED: usize = 0;
+
+/// Links to decide and corners to narrow, in board coordinates.
+pub(super) type Consequences = (Vec<(Edge, LinkType)>, Vec<(usize, usize, CornerType)>);
+
+impl Relations {
+    /// Node of a link of the window, given in window coordinates.
+    fn node(&self, edge: Edge) -> usize {
+        match edge {
+            Edge::H(row, col) => 1 + row * self.width + col,
+            Edge::V(row, col) => 1 + (self.height + 1) * self.width + row * (self.width + 1) + col,
+        }
+    }
+
+    /// Records the decided links and the corners that relate two links.
+    fn add_facts(
+        &mut self,
+        hlinks: impl Fn(usize, usize) -> LinkType,
+        vlinks: impl Fn(usize, usize) -> LinkType,
+        corners: impl Fn(usize, usize) -> CornerType,
+    ) -> Result<(), Contradiction> {
+        let (height, width) = (self.height, self.width);
+        for row in 0..=height {
+            for col in 0..width {
+                if hlinks(row, col) != LMaybe {
+                    let node = self.node(Edge::H(row, col));
+                    self.classes
+                        .union(node, UNLINKED, hlinks(row, col) == Link)?;
+                }
+            }
+        }
+        for row in 0..height {
+            for col in 0..=width {
+                if vlinks(row, col) != LMaybe {
+                    let node = self.node(Edge::V(row, col));
+                    self.classes
+                        .union(node, UNLINKED, vlinks(row, col) == Link)?;
+                }
+            }
+        }
+        for row in 0..2 * height {
+            for col in 0..2 * width {
+                let hlink = self.node(Edge::H(row / 2 + row % 2, col / 2));
+                let vlink = self.node(Edge::V(row / 2, col / 2 + col % 2));
+                match corners(row, col) {
+                    CZero | CTwo => {
+                        let linked = corners(row, col) == CTwo;
+                        self.classes.union(hlink, UNLINKED, linked)?;
+                        self.classes.union(vlink, UNLINKED, linked)?;
+                    }
+                    Even => self.classes.union(hlink, vlink, false)?,
+                    COne => self.classes.union(hlink, vlink, true)?,
+                    CMaybe | Less | Greater => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the links labelled alike in `half` are known to be related as
+    /// their labels say.
+    fn hold(&mut self, half: &HalfRule) -> bool {
+        let links = half.labelled_links();
+        links.iter().all(|&(edge, label)| {
+            let &(first, first_label) = links
+                .iter()
+                .find(|(_, other)| other.letter == label.letter)
+                .unwrap();
+            let (root, differs) = self.classes.find(self.node(edge));
+            let (first_root, first_differs) = self.classes.find(self.node(first));
+            root == first_root && differs ^ first_differs == label.opposite ^ first_label.opposite
+        })
+    }
+
+    /// Relates the links labelled alike in `half`.
+    fn relate(&mut self, half: &HalfRule) -> Result<(), Contradiction> {
+        let links = half.labelled_links();
+        for &(edge, label) in &links {
+            let &(first, first_label) = links
+                .iter()
+                .find(|(_, other)| other.letter == label.letter)
+                .unwrap();
+            let (node, first) = (self.node(edge), self.node(first));
+            self.classes
+                .union(node, first, label.opposite ^ first_label.opposite)?;
+        }
+        Ok(())
+    }
+
+    /// Links and corners that follow once `rule_out` applies, with its decided
+    /// elements and the relations between the links it labels: links decided
+    /// by their class, and corners whose two links are in the same class.
+    /// Fails if they cannot hold together.
+    pub(super) fn consequences(
+        &mut self,
+        rule_out: &HalfRule,
+    ) -> Result<Consequences, Contradiction> {
+        self.add_facts(
+            |row, col| rule_out.hlinks[row][col],
+            |row, col| rule_out.vlinks[row][col],
+            |row, col| rule_out.corners[row][col],
+        )?;
+        self.relate(rule_out)?;
+        let (height, width, row_s, col_s) = (self.height, self.width, self.row_s, self.col_s);
+        let (unlinked, unlinked_differs) = self.classes.find(UNLINKED);
+        let edges = (0..=height)
+            .flat_map(|row| (0..width).map(move |col| Edge::H(row, col)))
+            .chain((0..height).flat_map(|row| (0..=width).map(move |col| Edge::V(row, col))));
+        let mut links = Vec::new();
+        for edge in edges {
+            let (root, differs) = self.classes.find(self.node(edge));
+            if root == unlinked {
+                let board_edge = match edge {
+                    Edge::H(row, col) => Edge::H(row_s + row, col_s + col),
+                    Edge::V(row, col) => Edge::V(row_s + row, col_s + col),
+                };
+                let linked = differs != unlinked_differs;
+                links.push((board_edge, if linked { Link } else { Unlink }));
+            }
+        }
+        let mut corners = Vec::new();
+        for row in 0..2 * height {
+            for col in 0..2 * width {
+                let hlink = self.node(Edge::H(row / 2 + row % 2, col / 2));
+                let vlink = self.node(Edge::V(row / 2, col / 2 + col % 2));
+                let (h_root, h_differs) = self.classes.find(hlink);
+                let (v_root, v_differs) = self.classes.find(vlink);
+                if h_root == v_root {
+                    let corner = if h_differs == v_differs { Even } else { COne };
+                    corners.push((2 * row_s + row, 2 * col_s + col, corner));
+                }
+            }
+        }
+        Ok((links, corners))
+    }
+}
+
+impl Game {
+    /// Relations between the links of the window of `rule_in` anchored at
+    /// `(row_s, col_s)` if the links it labels alike are known to be equal,
+    /// or opposite for letters of different cases. Relations are known from
+    /// decided links and from corners, which relate their two links.
+    pub(super) fn labels_match(
+        &self,
+        rule_in: &HalfRule,
+        row_s: usize,
+        col_s: usize,
+    ) -> Result<Option<Relations>, Contradiction> {
+        let (height, width) = (rule_in.height, rule_in.width);
+        let mut relations = Relations {
+            classes: Classes::new(1 + (height + 1) * width + height * (width + 1)),
+            height,
+            width,
+            row_s,
+            col_s,
+        };
+        relations.add_facts(
+            |row, col| self.hlinks[row_s + row][col_s + col],
+            |row, col| self.vlinks[row_s + row][col_s + col],
+            |row, col| self.corners[2 * row_s + row][2 * col_s + col],
+        )?;
+        Ok(relations.hold(rule_in).then_some(relations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Rule;
+
+    /// Rule on a cell whose top link `a` is the opposite of its left link
+    /// `A`, and which finds its right link equal to its top link and its
+    /// bottom link opposite.
+    fn rule() -> Rule {
+        Rule::new(
+            "Labels",
+            "+aaaaaaa+\nA       .\nA       .\nA       .\n+.......+\n=>\n\
+             +aaaaaaa+\nA       a\nA       a\nA       a\n+AAAAAAA+",
+        )
+        .unwrap()
+    }
+
+    /// A blank board, with the rule anchored at its cell `(2, 2)`.
+    fn game() -> Game {
+        Game::new(5, 5, "y")
+    }
+
+    #[test]
+    fn labels_match_only_related_links() {
+        let rule = rule();
+        let mut game = game();
+        assert_eq!(game.try_apply_rule(&rule, 2, 2), Ok(None));
+        // The corner between the top and left links has one link.
+        game.write_corner(4, 4, COne);
+        assert!(game.clone().try_apply_rule(&rule, 2, 2).unwrap().is_some());
+        let mut game = self::game();
+        game.set_hlink(2, 2, Link);
+        game.set_vlink(2, 2, Link);
+        assert_eq!(game.try_apply_rule(&rule, 2, 2), Ok(None));
+        game.set_vlink(2, 2, Unlink);
+        assert!(game.try_apply_rule(&rule, 2, 2).unwrap().is_some());
+    }
+
+    #[test]
+    fn rules_write_derived_links_and_corners() {
+        let rule = rule();
+        let mut game = game();
+        game.write_corner(4, 4, COne);
+        assert_eq!(game.try_apply_rule(&rule, 2, 2), Ok(Some(true)));
+        // Top and right links are equal, top and bottom ones opposite.
+        assert_eq!(game.corners[4][5], Even);
+        assert_eq!(game.corners[5][4], Even);
+        assert_eq!(game.corners[5][5], COne);
+        let mut game = self::game();
+        game.set_hlink(2, 2, Link);
+        game.set_vlink(2, 2, Unlink);
+        assert_eq!(game.try_apply_rule(&rule, 2, 2), Ok(Some(true)));
+        assert_eq!(game.vlink(2, 3), Link);
+        assert_eq!(game.hlink(3, 2), Unlink);
+        assert_eq!(game.try_apply_rule(&rule, 2, 2), Ok(Some(false)));
+    }
+
+    #[test]
+    fn relations_contradicting_the_board_are_contradictions() {
+        let rule = rule();
+        let mut game = game();
+        game.set_hlink(2, 2, Link);
+        game.set_vlink(2, 2, Unlink);
+        game.set_vlink(2, 3, Unlink);
+        assert_eq!(game.try_apply_rule(&rule, 2, 2), Err(Contradiction));
+    }
+}
@@ -0,0 +1,82 @@
+use super::Rule;
+use super::element::*;
+use super::grid::Grid;
+use super::rule::HalfRule;
+
+/// Sides of a window, in the order in which quarter turns bring them to the
+/// top, see [`Grid::rotated_90`].
+const SIDES: [&str; 4] = ["top row", "right column", "bottom row", "left column"];
+
+/// Position of the first value of `outs` that is not a refinement of the
+/// value of `ins` at the same position.
+fn widened<T: Mask>(ins: &Grid<T>, outs: &Grid<T>) -> Option<(usize, usize)> {
+    ins.rows()
+        .zip(outs.rows())
+        .enumerate()
+        .find_map(|(row, (ins, outs))| {
+            ins.iter()
+                .zip(outs)
+                .position(|(value_in, value_out)| value_out.to_mask() & !value_in.to_mask() != 0)
+                .map(|col| (row, col))
+        })
+}
+
+impl HalfRule {
+    /// Whether the top row of cells constrains nothing: blank clues without
+    /// wildcards, unknown colours and undecided corners and links, without
+    /// labels. The links below the row also border the next row, so they are
+    /// left out.
+    pub(super) fn top_row_is_free(&self) -> bool {
+        let free_link =
+            |pattern, label: Option<_>| pattern == Pattern::refining(LMaybe) && label.is_none();
+        (0..self.width).all(|col| {
+            self.cell_patterns[0][col] == Pattern::refining(Empty)
+                && !self.outside[0][col]
+                && self.colors[0][col] == Unknown
+                && free_link(self.hlink_patterns[0][col], self.hlink_labels[0][col])
+                && self.corners[0][2 * col..2 * col + 2] == [CMaybe; 2]
+                && self.corners[1][2 * col..2 * col + 2] == [CMaybe; 2]
+        }) && (0..=self.width)
+            .all(|col| free_link(self.vlink_patterns[0][col], self.vlink_labels[0][col]))
+    }
+}
+
+impl Rule {
+    /// Problems of the rule that do not prevent using it: doing nothing,
+    /// widening an element of its input, which a narrowing rule never does,
+    /// and a window larger than needed, with a side that constrains nothing.
+    pub fn lint(&self) -> Vec<String> {
+        let (rule_in, rule_out) = (self.rule_in(), self.rule_out());
+        let mut problems = Vec::new();
+        if rule_in == rule_out {
+            problems.push("does nothing, its output is its input".to_owned());
+        }
+        let widened = [
+            ("clue", widened(&rule_in.cells, &rule_out.cells)),
+            ("colour", widened(&rule_in.colors, &rule_out.colors)),
+            (
+                "horizontal link",
+                widened(&rule_in.hlinks, &rule_out.hlinks),
+            ),
+            ("vertical link", widened(&rule_in.vlinks, &rule_out.vlinks)),
+            ("corner", widened(&rule_in.corners, &rule_out.corners)),
+        ];
+        if let Some((element, Some((row, col)))) =
+            widened.into_iter().find(|(_, position)| position.is_some())
+        {
+            problems.push(format!(
+                "widens the {element} at ({row}, {col}), its output should narrow its input"
+            ));
+        }
+        let (mut rule_in, mut rule_out) = (rule_in.clone(), rule_out.clone());
+        for side in SIDES {
+            if rule_in.height > 1 && rule_in.top_row_is_free() && rule_out.top_row_is_free() {
+                problems.push(format!(
+                    "has a {side} that constrains nothing, its window could be smaller"
+                ));
+            }
+            (rule_in, rule_out) = (rule_in.rotated_90(), rule_out.rotated_90());
+        }
+        problems
+    }
+}
@@ -0,0 +1,134 @@
+use super::element::*;
+use super::grid::Grid;
+use super::{Game, Rule};
+
+/// Element of a rule window that must match the board for the rule to apply,
+/// at `(row, col)` within the window.
+#[derive(Clone, Copy, Debug)]
+enum Key {
+    Cell(usize, usize, CellType),
+    Color(usize, usize, ColorType),
+    HLink(usize, usize, LinkType),
+    VLink(usize, usize, LinkType),
+    Corner(usize, usize, CornerType),
+}
+
+/// Picks the element of `rule_in` least likely to match a board element.
+/// Clues are the rarest and never change, links and corners become more
+/// common as the solve progresses. Rules without any selective element get
+/// no key and are tried everywhere.
+fn key_of(rule: &Rule) -> Option<Key> {
+    let rule_in = rule.rule_in();
+    let mut best: Option<(u8, Key)> = None;
+    let mut consider = |rank: u8, key: Key| {
+        if best.is_none_or(|(best_rank, _)| rank < best_rank) {
+            best = Some((rank, key));
+        }
+    };
+    for (row, cells) in rule_in.cells.rows().enumerate() {
+        for (col, &cell) in cells.iter().enumerate() {
+            if cell != Empty {
+                consider(0, Key::Cell(row, col, cell));
+            }
+        }
+    }
+    for (row, colors) in rule_in.colors.rows().enumerate() {
+        for (col, &color) in colors.iter().enumerate() {
+            if color != Unknown {
+                consider(3, Key::Color(row, col, color));
+            }
+        }
+    }
+    for (row, links) in rule_in.hlinks.rows().enumerate() {
+        for (col, &link) in links.iter().enumerate() {
+            match link {
+                Link => consider(1, Key::HLink(row, col, link)),
+                Unlink => consider(3, Key::HLink(row, col, link)),
+                LMaybe => {}
+            }
+        }
+    }
+    for (row, links) in rule_in.vlinks.rows().enumerate() {
+        for (col, &link) in links.iter().enumerate() {
+            match link {
+                Link => consider(1, Key::VLink(row, col, link)),
+                Unlink => consider(3, Key::VLink(row, col, link)),
+                LMaybe => {}
+            }
+        }
+    }
+    for (row, corners) in rule_in.corners.rows().enumerate() {
+        for (col, &corner) in corners.iter().enumerate() {
+            match corner {
+                COne | CTwo => consider(2, Key::Corner(row, col, corner)),
+                CZero | Greater => consider(3, Key::Corner(row, col, corner)),
+                CMaybe | Even | Less => {}
+            }
+        }
+    }
+    best.map(|(_, key)| key)
+}
+
+/// Anchors that put the key at `(key_row, key_col)` of the window over an
+/// element of `grid` it matches. Corners come two per cell in each
+/// direction, hence `step`.
+fn anchors_of<T: Copy>(
+    grid: &Grid<T>,
+    (key_row, key_col): (usize, usize),
+    step: usize,
+    matches: impl Fn(T) -> bool,
+) -> Vec<(usize, usize)> {
+    let mut anchors = Vec::new();
+    for (row, elements) in grid.rows().enumerate().skip(key_row) {
+        if (row - key_row) % step != 0 {
+            continue;
+        }
+        for (col, &element) in elements.iter().enumerate().skip(key_col) {
+            if (col - key_col) % step == 0 && matches(element) {
+                anchors.push(((row - key_row) / step, (col - key_col) / step));
+            }
+        }
+    }
+    anchors
+}
+
+/// Rules compiled for matching: each rule is indexed by a key element and
+/// only tried at the anchors where the board matches that element, instead of
+/// at every anchor.
+pub struct Matcher {
+    keys: Vec<Option<Key>>,
+}
+
+impl Matcher {
+    pub fn new(rules: &[Rule]) -> Matcher {
+        Matcher {
+            keys: rules.iter().map(key_of).collect(),
+        }
+    }
+
+    /// Anchors, in row-major order, where the rule at `index` may match
+    /// `game`. Anchors too close to the bottom or right for the window are
+    /// left to [`Game::try_apply_rule`] to reject.
+    pub fn anchors(&self, game: &Game, index: usize) -> Vec<(usize, usize)> {
+        match self.keys[index] {
+            None => (0..game.height)
+                .flat_map(|row| (0..game.width).map(move |col| (row, col)))
+                .collect(),
+            Some(Key::Cell(row, col, cell)) => {
+                anchors_of(&game.cells, (row, col), 1, |other| cell >= other)
+            }
+            Some(Key::Color(row, col, color)) => {
+                anchors_of(&game.colors, (row, col), 1, |other| color >= other)
+            }
+            Some(Key::HLink(row, col, link)) => {
+                anchors_of(&game.hlinks, (row, col), 1, |other| link >= other)
+            }
+            Some(Key::VLink(row, col, link)) => {
+                anchors_of(&game.vlinks, (row, col), 1, |other| link >= other)
+            }
+            Some(Key::Corner(row, col, corner)) => {
+                anchors_of(&game.corners, (row, col), 2, |other| corner >= other)
+            }
+        }
+    }
+}
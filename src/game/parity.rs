@@ -0,0 +1,46 @@
+use super::element::*;
+use super::{Contradiction, Game};
+
+impl Game {
+    /// A closed loop crosses every row of cells through an even number of
+    /// vertical links, and every column through an even number of horizontal
+    /// links. Decides the last undecided link of such a line to make its count
+    /// even. Returns whether the board changed.
+    pub(super) fn constrain_parity(&mut self) -> Result<bool, Contradiction> {
+        let mut modified = false;
+        for row in 0..self.height {
+            let line = (0..=self.width).map(|col| self.vlinks[row][col]);
+            if let Some((col, link)) = last_of_line(line)? {
+                modified |= self.write_vlink(row, col, link);
+            }
+        }
+        for col in 0..self.width {
+            let line = (0..=self.height).map(|row| self.hlinks[row][col]);
+            if let Some((row, link)) = last_of_line(line)? {
+                modified |= self.write_hlink(row, col, link);
+            }
+        }
+        Ok(modified)
+    }
+}
+
+/// The position and forced state of the only undecided link of a line, if it
+/// has exactly one.
+fn last_of_line(
+    line: impl Iterator<Item = LinkType>,
+) -> Result<Option<(usize, LinkType)>, Contradiction> {
+    let mut links = 0;
+    let mut undecided = Vec::new();
+    for (index, link) in line.enumerate() {
+        match link {
+            Link => links += 1,
+            LMaybe => undecided.push(index),
+            Unlink => (),
+        }
+    }
+    match undecided[..] {
+        [] if links % 2 == 1 => Err(Contradiction),
+        [index] => Ok(Some((index, if links % 2 == 1 { Link } else { Unlink }))),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,326 @@
+use super::journal::Change;
+use super::matcher::Matcher;
+use super::{Contradiction, Game, Rule};
+use std::ops::{Range, RangeInclusive};
+use std::time::{Duration, Instant};
+
+/// What [`Game::propagate`] did to the board.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct Propagation {
+    /// Rule applications that modified the board.
+    pub firings: usize,
+    /// Elements modified by the built-in constraints on cells, corners,
+    /// vertices, colours and parity.
+    pub builtin_changes: usize,
+}
+
+/// What a rule did during [`Game::propagate_profiled`].
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RuleStats {
+    /// Anchors at which the rule was tried.
+    pub tried: usize,
+    /// Anchors at which it matched, including those where it found a
+    /// contradiction.
+    pub matched: usize,
+    /// Anchors at which it modified the board.
+    pub changed: usize,
+    /// Time spent trying the rule.
+    pub elapsed: Duration,
+}
+
+impl RuleStats {
+    pub fn add(&mut self, other: &RuleStats) {
+        self.tried += other.tried;
+        self.matched += other.matched;
+        self.changed += other.changed;
+        self.elapsed += other.elapsed;
+    }
+}
+
+/// Anchors of the windows of `height` by `width` cells that contain the
+/// element modified by `change`, as ranges of rows and columns. Windows do
+/// not contain vertices.
+fn covering_anchors(
+    change: Change,
+    height: usize,
+    width: usize,
+) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+    let (row, col, extra_row, extra_col) = match change {
+        Change::Cell(row, col, ..) | Change::Color(row, col, ..) => (row, col, 0, 0),
+        Change::HLink(row, col, ..) => (row, col, 1, 0),
+        Change::VLink(row, col, ..) => (row, col, 0, 1),
+        Change::Corner(row, col, ..) => (row / 2, col / 2, 0, 0),
+        Change::Vertex(..) | Change::Join(..) | Change::Split(..) => return None,
+    };
+    Some((
+        (row + 1).saturating_sub(height + extra_row)..=row,
+        (col + 1).saturating_sub(width + extra_col)..=col,
+    ))
+}
+
+/// Number of `changes` that modified an element of the board, leaving out
+/// those of the colour classes.
+fn element_changes(changes: &[Change]) -> usize {
+    changes
+        .iter()
+        .filter(|change| !matches!(change, Change::Join(..) | Change::Split(..)))
+        .count()
+}
+
+/// Vertices at the ends of the link modified by `change`.
+fn link_ends(change: Change) -> Option<[(usize, usize); 2]> {
+    match change {
+        Change::HLink(row, col, ..) => Some([(row, col), (row, col + 1)]),
+        Change::VLink(row, col, ..) => Some([(row, col), (row + 1, col)]),
+        _ => None,
+    }
+}
+
+impl Game {
+    /// Applies the first rule, in the given order, that modifies the board at
+    /// some anchor, and returns its index together with the anchor.
+    pub fn apply_any_rule(
+        &mut self,
+        rules: &[Rule],
+    ) -> Result<Option<(usize, usize, usize)>, Contradiction> {
+        let matcher = Matcher::new(rules);
+        for (index, rule) in rules.iter().enumerate() {
+            for (row_s, col_s) in matcher.anchors(self, index) {
+                if self.try_apply_rule(rule, row_s, col_s)? == Some(true) {
+                    return Ok(Some((index, row_s, col_s)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Applies rules, keeps corners consistent with links and clues, vertices
+    /// at degree 0 or 2, cell colours consistent with links and rows and
+    /// columns crossing the loop an even number of times, until the board no
+    /// longer changes. Returns the number of rule applications that modified
+    /// the board, and of elements modified by the built-in constraints.
+    ///
+    /// Consecutive rules of equal priority form a tier, and cheaper work
+    /// always comes first: the built-in constraints run until they no longer
+    /// change the board, then the cheapest tier with work left takes one
+    /// pass, and any change it makes sends the search back to the built-in
+    /// constraints and the cheaper tiers. A tier first sweeps its rules over
+    /// the anchors where they may match; after that, it only examines the
+    /// windows containing an element modified since its previous pass.
+    /// Likewise cells and vertices are constrained all at first and then only
+    /// around modified elements, while colours and parity are constrained over
+    /// the whole board each time.
+    pub fn propagate(&mut self, rules: &[Rule]) -> Result<Propagation, Contradiction> {
+        self.propagate_with(rules, None)
+    }
+
+    /// Same as [`Game::propagate`], also adding to `stats[i]` what `rules[i]`
+    /// did. Timing every attempt makes propagation noticeably slower.
+    pub fn propagate_profiled(
+        &mut self,
+        rules: &[Rule],
+        stats: &mut [RuleStats],
+    ) -> Result<Propagation, Contradiction> {
+        self.propagate_with(rules, Some(stats))
+    }
+
+    /// Tries `rules[index]` at an anchor, recording the attempt in `stats` if
+    /// given, and returns whether it modified the board.
+    fn try_rule(
+        &mut self,
+        rules: &[Rule],
+        index: usize,
+        (row_s, col_s): (usize, usize),
+        stats: &mut Option<&mut [RuleStats]>,
+    ) -> Result<bool, Contradiction> {
+        let Some(stats) = stats else {
+            return Ok(self.try_apply_rule(&rules[index], row_s, col_s)? == Some(true));
+        };
+        let start = Instant::now();
+        let result = self.try_apply_rule(&rules[index], row_s, col_s);
+        let stats = &mut stats[index];
+        stats.elapsed += start.elapsed();
+        stats.tried += 1;
+        stats.matched += !matches!(result, Ok(None)) as usize;
+        stats.changed += matches!(result, Ok(Some(true))) as usize;
+        Ok(result? == Some(true))
+    }
+
+    fn propagate_with(
+        &mut self,
+        rules: &[Rule],
+        mut stats: Option<&mut [RuleStats]>,
+    ) -> Result<Propagation, Contradiction> {
+        let matcher = Matcher::new(rules);
+        let mut tiers: Vec<Range<usize>> = Vec::new();
+        for (index, rule) in rules.iter().enumerate() {
+            match tiers.last_mut() {
+                Some(tier) if rules[tier.start].priority() == rule.priority() => tier.end += 1,
+                _ => tiers.push(index..index + 1),
+            }
+        }
+        // Changes before its checkpoint have been examined by a tier, which
+        // has no checkpoint before its first sweep.
+        let mut tier_checkpoints = vec![None; tiers.len()];
+        let mut progress = Propagation::default();
+        let mut checkpoint = self.checkpoint();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                self.constrain_cell(row, col)?;
+            }
+        }
+        for row in 0..=self.height {
+            for col in 0..=self.width {
+                self.constrain_vertex(row, col)?;
+            }
+        }
+        self.constrain_colors()?;
+        self.constrain_parity()?;
+        progress.builtin_changes += element_changes(self.changes_since(checkpoint));
+        // Anchors already examined for the current rule of the current pass
+        // are marked with its stamp.
+        let (height, width) = (self.height, self.width);
+        let mut stamps = vec![vec![0; width]; height];
+        let mut stamp = 0;
+        loop {
+            let dirty = self.changes_since(checkpoint).to_vec();
+            checkpoint = self.checkpoint();
+            if !dirty.is_empty() {
+                let before = self.checkpoint();
+                stamp += 1;
+                for &change in &dirty {
+                    let Some((rows, cols)) = covering_anchors(change, 1, 1) else {
+                        continue;
+                    };
+                    for row in rows.filter(|&row| row < height) {
+                        for col in cols.clone().filter(|&col| col < width) {
+                            if stamps[row][col] != stamp {
+                                stamps[row][col] = stamp;
+                                self.constrain_cell(row, col)?;
+                            }
+                        }
+                    }
+                }
+                for (row, col) in dirty.iter().copied().filter_map(link_ends).flatten() {
+                    self.constrain_vertex(row, col)?;
+                }
+                self.constrain_colors()?;
+                self.constrain_parity()?;
+                progress.builtin_changes += element_changes(self.changes_since(before));
+                continue;
+            }
+            let Some(tier) = (0..tiers.len()).find(|&tier| {
+                tier_checkpoints[tier]
+                    .is_none_or(|tier_checkpoint| !self.changes_since(tier_checkpoint).is_empty())
+            }) else {
+                return Ok(progress);
+            };
+            let Some(tier_checkpoint) = tier_checkpoints[tier].replace(self.checkpoint()) else {
+                for index in tiers[tier].clone() {
+                    for anchor in matcher.anchors(self, index) {
+                        if self.try_rule(rules, index, anchor, &mut stats)? {
+                            progress.firings += 1;
+                        }
+                    }
+                }
+                continue;
+            };
+            let dirty = self.changes_since(tier_checkpoint).to_vec();
+            for index in tiers[tier].clone() {
+                let rule_in = rules[index].rule_in();
+                stamp += 1;
+                for &change in &dirty {
+                    let Some((rows, cols)) =
+                        covering_anchors(change, rule_in.height, rule_in.width)
+                    else {
+                        continue;
+                    };
+                    for row_s in rows.filter(|&row_s| row_s < height) {
+                        for col_s in cols.clone().filter(|&col_s| col_s < width) {
+                            if stamps[row_s][col_s] == stamp {
+                                continue;
+                            }
+                            stamps[row_s][col_s] = stamp;
+                            if self.try_rule(rules, index, (row_s, col_s), &mut stats)? {
+                                progress.firings += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Same as [`Game::propagate`], but tries every rule at every anchor and
+    /// constrains every cell, vertex, colour and line in each round. Kept as a
+    /// reference for benchmarking and testing the matcher.
+    pub fn propagate_brute_force(&mut self, rules: &[Rule]) -> Result<Propagation, Contradiction> {
+        let mut progress = Propagation::default();
+        loop {
+            let before = progress;
+            let checkpoint = self.checkpoint();
+            let mut constrained = false;
+            for row in 0..self.height {
+                for col in 0..self.width {
+                    constrained |= self.constrain_cell(row, col)?;
+                }
+            }
+            for row in 0..=self.height {
+                for col in 0..=self.width {
+                    constrained |= self.constrain_vertex(row, col)?;
+                }
+            }
+            constrained |= self.constrain_colors()?;
+            constrained |= self.constrain_parity()?;
+            progress.builtin_changes += element_changes(self.changes_since(checkpoint));
+            for rule in rules {
+                for row_s in 0..self.height {
+                    for col_s in 0..self.width {
+                        if self.try_apply_rule(rule, row_s, col_s)? == Some(true) {
+                            progress.firings += 1;
+                        }
+                    }
+                }
+            }
+            if progress.firings == before.firings && !constrained {
+                return Ok(progress);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puzzle::parse_puzzle;
+    use crate::rule_file::{DEFAULT_RULE_FILES, flatten, load_rule_files};
+
+    #[test]
+    fn propagate_reaches_the_fixpoint_of_brute_force() {
+        let rules = flatten(&load_rule_files(DEFAULT_RULE_FILES).unwrap());
+        for puzzle in [
+            "5x5:b2b2a1d222a202b333a",
+            "12x12:a11c112b3a0b0a0132a2100c0a1b3d0c0b3b0a0123a0a210b0b2b02c00122e0b01c01023f0g22a1a0d0b0a0c00c0b00b0",
+            // Stalled with these rules.
+            ".1....1....3/.0....01.2../1.0........./...........3/.....12..0../.......2...2/\
+             .....122..../....0....0.0/2.........../............/.......0..../...........0",
+            "..1.2....23./....0.0.01../..2.0......./0.......01.2/....00....../..1...0.0.../\
+             0...1...0.../....10.0..../0.......1..0/............/...03..2.12./..0....0.3.1",
+            // A 3 between two 0s.
+            "...../.030./...../...../.....",
+        ] {
+            let mut indexed = parse_puzzle(puzzle).unwrap();
+            let mut brute_force = indexed.clone();
+            let indexed_result = indexed.propagate(&rules);
+            let brute_force_result = brute_force.propagate_brute_force(&rules);
+            assert_eq!(
+                indexed_result.is_ok(),
+                brute_force_result.is_ok(),
+                "{puzzle}"
+            );
+            if indexed_result.is_ok() {
+                assert_eq!(indexed.hlinks, brute_force.hlinks, "{puzzle}");
+                assert_eq!(indexed.vlinks, brute_force.vlinks, "{puzzle}");
+            }
+        }
+    }
+}
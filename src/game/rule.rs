@@ -1,66 +1,252 @@
+use super::Game;
 use super::element::*;
+use super::grid::Grid;
+use super::vertex::Edge;
+use std::collections::HashSet;
+use std::io::{self, Write};
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+/// Clue glyphs besides `' '` and the digits, and the clues they match. A
+/// blank cell, which has no clue, is matched by `' '` and `'-'` only, and a
+/// cell of the padding around the grid by `'#'`, see [`HalfRule::outside`].
+const CELL_WILDCARDS: [(char, &[CellType]); 6] = [
+    ('*', &[Zero, One, Two, Three]),
+    ('-', &[Empty]),
+    ('n', &[One, Two, Three]),
+    ('l', &[Zero, One]),
+    ('m', &[One, Two]),
+    ('h', &[Two, Three]),
+];
+
+/// Horizontal link glyphs besides `'.'`, `'='` and `' '`: linked or
+/// undecided, and unlinked or undecided.
+const HLINK_WILDCARDS: [(char, &[LinkType]); 2] =
+    [('-', &[Link, LMaybe]), (',', &[Unlink, LMaybe])];
+
+/// Vertical link glyphs besides `'.'`, `'$'` and `' '`, as above.
+const VLINK_WILDCARDS: [(char, &[LinkType]); 2] =
+    [('|', &[Link, LMaybe]), (',', &[Unlink, LMaybe])];
+
+/// Glyph of a wildcard `pattern`, or `None` if it is the pattern of the
+/// exact glyph of `value`.
+fn wildcard_glyph<T: Mask>(wildcards: &[(char, &[T])], value: T, pattern: Pattern) -> Option<char> {
+    if pattern == Pattern::refining(value) {
+        return None;
+    }
+    wildcards
+        .iter()
+        .find(|(_, values)| Pattern::of(values) == pattern)
+        .map(|&(glyph, _)| glyph)
+}
+
+/// Whether `patterns` has a wildcard that is not the one of `same` at the
+/// same position.
+fn other_wildcard<T: Mask + Copy>(
+    values: &Grid<T>,
+    patterns: &Grid<Pattern>,
+    same: &Grid<Pattern>,
+) -> bool {
+    values
+        .rows()
+        .zip(patterns.rows().zip(same.rows()))
+        .flat_map(|(values, (patterns, same))| values.iter().zip(patterns.iter().zip(same)))
+        .any(|(&value, (&pattern, &same))| pattern != Pattern::refining(value) && pattern != same)
+}
+
+/// Label of a link in a rule. Links labelled with the same letter are equal,
+/// or opposite when the letter is in upper case for only one of them.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub(super) struct Label {
+    pub(super) letter: u8,
+    pub(super) opposite: bool,
+}
+
+impl Label {
+    fn of(glyph: char) -> Label {
+        Label {
+            letter: glyph.to_ascii_lowercase() as u8,
+            opposite: glyph.is_ascii_uppercase(),
+        }
+    }
+
+    fn glyph(self) -> char {
+        if self.opposite {
+            self.letter.to_ascii_uppercase() as char
+        } else {
+            self.letter as char
+        }
+    }
+}
+
+/// One side of a rule. Elements are matched by their value, or for cells and
+/// links by their pattern when it is a wildcard, whose value is then the
+/// loosest one. Labelled links are undecided and related to each other, see
+/// [`Game::labels_match`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub(super) struct HalfRule {
     pub(super) height: usize,
     pub(super) width: usize,
-    pub(super) cells: Vec<Vec<CellType>>,
-    pub(super) hlinks: Vec<Vec<LinkType>>,
-    pub(super) vlinks: Vec<Vec<LinkType>>,
-    pub(super) corners: Vec<Vec<CornerType>>,
+    pub(super) cells: Grid<CellType>,
+    pub(super) colors: Grid<ColorType>,
+    pub(super) hlinks: Grid<LinkType>,
+    pub(super) vlinks: Grid<LinkType>,
+    pub(super) corners: Grid<CornerType>,
+    pub(super) cell_patterns: Grid<Pattern>,
+    pub(super) hlink_patterns: Grid<Pattern>,
+    pub(super) vlink_patterns: Grid<Pattern>,
+    /// Cells marked `'#'`, which only match the padding around the grid.
+    pub(super) outside: Grid<bool>,
+    pub(super) hlink_labels: Grid<Option<Label>>,
+    pub(super) vlink_labels: Grid<Option<Label>>,
+    pub(super) has_wildcards: bool,
+    pub(super) has_labels: bool,
 }
 
 impl HalfRule {
-    fn new(rule_str: &str) -> HalfRule {
+    fn new(rule_str: &str) -> Result<HalfRule, String> {
         let lines = rule_str
             .trim()
             .lines()
             .map(str::as_bytes)
             .collect::<Vec<_>>();
-        assert!(lines.len() % 4 == 1);
+        if lines.len() % 4 != 1 {
+            return Err(format!(
+                "a window should have 4k+1 lines, found {}",
+                lines.len()
+            ));
+        }
         let height = (lines.len() - 1) / 4;
-        assert!(lines[0].len() % 8 == 1);
+        if lines[0].len() % 8 != 1 {
+            return Err(format!(
+                "a window should have 8k+1 columns, found {}",
+                lines[0].len()
+            ));
+        }
         let width = (lines[0].len() - 1) / 8;
-        assert!(lines.iter().all(|line| line.len() == width * 8 + 1));
-        let mut cells = vec![vec![Empty; width]; height];
+        if height == 0 || width == 0 {
+            return Err(format!(
+                "a window should have cells, found {height}x{width}"
+            ));
+        }
+        if let Some(row) = lines.iter().position(|line| line.len() != width * 8 + 1) {
+            return Err(format!(
+                "line {} has {} columns, expected {}",
+                row + 1,
+                lines[row].len(),
+                width * 8 + 1
+            ));
+        }
+        let glyph = |row: usize, col: usize| lines[row][col] as char;
+        let unexpected = |row: usize, col: usize| {
+            format!(
+                "unexpected glyph {:?} at line {}, column {}",
+                glyph(row, col),
+                row + 1,
+                col + 1
+            )
+        };
+        let mut has_wildcards = false;
+        let mut cells = Grid::new(height, width, Empty);
+        let mut cell_patterns = Grid::new(height, width, Pattern::refining(Empty));
+        let mut outside = Grid::new(height, width, false);
         for row in 0..height {
             for col in 0..width {
-                cells[row][col] = match lines[row * 4 + 2][col * 8 + 4] as char {
+                let (line, column) = (row * 4 + 2, col * 8 + 4);
+                cells[row][col] = match glyph(line, column) {
                     ' ' => Empty,
                     '0' => Zero,
                     '1' => One,
                     '2' => Two,
                     '3' => Three,
-                    _ => panic!(),
+                    '#' => {
+                        outside[row][col] = true;
+                        has_wildcards = true;
+                        Empty
+                    }
+                    other => {
+                        let Some((_, values)) = CELL_WILDCARDS.iter().find(|(g, _)| *g == other)
+                        else {
+                            return Err(unexpected(line, column));
+                        };
+                        cell_patterns[row][col] = Pattern::of(values);
+                        has_wildcards = true;
+                        continue;
+                    }
+                };
+                cell_patterns[row][col] = Pattern::refining(cells[row][col]);
+            }
+        }
+        let mut colors = Grid::new(height, width, Unknown);
+        for row in 0..height {
+            for col in 0..width {
+                colors[row][col] = match glyph(row * 4 + 2, col * 8 + 2) {
+                    ' ' => Unknown,
+                    'I' => Inside,
+                    'O' => Outside,
+                    _ => return Err(unexpected(row * 4 + 2, col * 8 + 2)),
                 }
             }
         }
-        let mut hlinks = vec![vec![LMaybe; width]; height + 1];
-        let mut vlinks = vec![vec![LMaybe; width + 1]; height];
-        let mut corners = vec![vec![CMaybe; 2 * width]; 2 * height];
+        let mut hlinks = Grid::new(height + 1, width, LMaybe);
+        let mut vlinks = Grid::new(height, width + 1, LMaybe);
+        let mut hlink_patterns = Grid::new(height + 1, width, Pattern::refining(LMaybe));
+        let mut vlink_patterns = Grid::new(height, width + 1, Pattern::refining(LMaybe));
+        let mut hlink_labels = Grid::new(height + 1, width, None);
+        let mut vlink_labels = Grid::new(height, width + 1, None);
+        let mut has_labels = false;
+        let mut corners = Grid::new(2 * height, 2 * width, CMaybe);
         for row in 0..=height {
             for col in 0..width {
-                hlinks[row][col] = match lines[row * 4][col * 8 + 4] as char {
+                let (line, column) = (row * 4, col * 8 + 4);
+                hlinks[row][col] = match glyph(line, column) {
                     '.' => LMaybe,
                     '=' => Link,
                     ' ' => Unlink,
-                    _ => panic!(),
+                    letter if letter.is_ascii_alphabetic() => {
+                        hlink_labels[row][col] = Some(Label::of(letter));
+                        has_labels = true;
+                        LMaybe
+                    }
+                    other => {
+                        let Some((_, values)) = HLINK_WILDCARDS.iter().find(|(g, _)| *g == other)
+                        else {
+                            return Err(unexpected(line, column));
+                        };
+                        hlink_patterns[row][col] = Pattern::of(values);
+                        has_wildcards = true;
+                        continue;
+                    }
                 };
+                hlink_patterns[row][col] = Pattern::refining(hlinks[row][col]);
             }
         }
         for row in 0..height {
             for col in 0..=width {
-                vlinks[row][col] = match lines[row * 4 + 2][col * 8] as char {
+                let (line, column) = (row * 4 + 2, col * 8);
+                vlinks[row][col] = match glyph(line, column) {
                     '.' => LMaybe,
                     '$' => Link,
                     ' ' => Unlink,
-                    _ => panic!(),
+                    letter if letter.is_ascii_alphabetic() => {
+                        vlink_labels[row][col] = Some(Label::of(letter));
+                        has_labels = true;
+                        LMaybe
+                    }
+                    other => {
+                        let Some((_, values)) = VLINK_WILDCARDS.iter().find(|(g, _)| *g == other)
+                        else {
+                            return Err(unexpected(line, column));
+                        };
+                        vlink_patterns[row][col] = Pattern::of(values);
+                        has_wildcards = true;
+                        continue;
+                    }
                 };
+                vlink_patterns[row][col] = Pattern::refining(vlinks[row][col]);
             }
         }
         for row in 0..2 * height {
             for col in 0..2 * width {
-                corners[row][col] = match lines[row * 2 + 1][col * 4 + 2] as char {
+                corners[row][col] = match glyph(row * 2 + 1, col * 4 + 2) {
                     ' ' => CMaybe,
                     '0' => CZero,
                     '1' => COne,
@@ -68,176 +254,294 @@ impl HalfRule {
                     'E' => Even,
                     'L' => Less,
                     'G' => Greater,
-                    _ => panic!(),
+                    _ => return Err(unexpected(row * 2 + 1, col * 4 + 2)),
                 };
             }
         }
-        HalfRule {
+        Ok(HalfRule {
             height,
             width,
             cells,
+            colors,
             hlinks,
             vlinks,
             corners,
+            cell_patterns,
+            hlink_patterns,
+            vlink_patterns,
+            outside,
+            hlink_labels,
+            vlink_labels,
+            has_wildcards,
+            has_labels,
+        })
+    }
+
+    /// Whether the wildcards and outside cells of the window anchored at
+    /// `(row_s, col_s)` match `game`. The other elements are left to
+    /// [`Game::try_apply_rule`].
+    pub(super) fn wildcards_match(&self, game: &Game, row_s: usize, col_s: usize) -> bool {
+        (0..self.height).all(|row| {
+            (0..self.width).all(|col| {
+                let (board_row, board_col) = (row_s + row, col_s + col);
+                self.cell_patterns[row][col].matches(game.cells[board_row][board_col])
+                    && (!self.outside[row][col] || game.is_padding(board_row, board_col))
+            }) && (0..=self.width).all(|col| {
+                self.vlink_patterns[row][col].matches(game.vlinks[row_s + row][col_s + col])
+            })
+        }) && (0..=self.height).all(|row| {
+            (0..self.width).all(|col| {
+                self.hlink_patterns[row][col].matches(game.hlinks[row_s + row][col_s + col])
+            })
+        })
+    }
+
+    /// Labelled links of the window, as edges within the window.
+    pub(super) fn labelled_links(&self) -> Vec<(Edge, Label)> {
+        let hlinks = self
+            .hlink_labels
+            .rows()
+            .enumerate()
+            .flat_map(|(row, labels)| {
+                labels
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(col, label)| Some((Edge::H(row, col), (*label)?)))
+            });
+        let vlinks = self
+            .vlink_labels
+            .rows()
+            .enumerate()
+            .flat_map(|(row, labels)| {
+                labels
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(col, label)| Some((Edge::V(row, col), (*label)?)))
+            });
+        hlinks.chain(vlinks).collect()
+    }
+
+    /// Glyph of the wildcard of the cell at `(row, col)`, if it has one.
+    pub(super) fn cell_wildcard(&self, row: usize, col: usize) -> Option<char> {
+        wildcard_glyph(
+            &CELL_WILDCARDS,
+            self.cells[row][col],
+            self.cell_patterns[row][col],
+        )
+    }
+
+    /// Glyph of the label or wildcard of the horizontal link at `(row, col)`,
+    /// if it has one.
+    pub(super) fn hlink_glyph(&self, row: usize, col: usize) -> Option<char> {
+        if let Some(label) = self.hlink_labels[row][col] {
+            return Some(label.glyph());
+        }
+        wildcard_glyph(
+            &HLINK_WILDCARDS,
+            self.hlinks[row][col],
+            self.hlink_patterns[row][col],
+        )
+    }
+
+    pub(super) fn vlink_glyph(&self, row: usize, col: usize) -> Option<char> {
+        if let Some(label) = self.vlink_labels[row][col] {
+            return Some(label.glyph());
         }
+        wildcard_glyph(
+            &VLINK_WILDCARDS,
+            self.vlinks[row][col],
+            self.vlink_patterns[row][col],
+        )
     }
 
-    fn full_print_row(&self, row: usize) {
-        full_print_row(
+    pub(super) fn full_render_row(&self, out: &mut impl Write, row: usize) -> io::Result<()> {
+        let mut line = Vec::new();
+        full_render_row(
+            &mut line,
             self.height,
             self.width,
             &self.cells,
+            &self.colors,
             &self.hlinks,
             &self.vlinks,
             &self.corners,
             row,
             false,
-        );
-    }
-
-    fn rotated_90(&self) -> HalfRule {
-        let height = self.width;
-        let width = self.height;
-        let mut cells = vec![vec![Empty; width]; height];
-        let mut hlinks = vec![vec![LMaybe; width]; height + 1];
-        let mut vlinks = vec![vec![LMaybe; width + 1]; height];
-        let mut corners = vec![vec![CMaybe; 2 * width]; 2 * height];
-        #[expect(clippy::needless_range_loop)]
-        for row in 0..height {
-            for col in 0..width {
-                cells[row][col] = self.cells[col][height - 1 - row];
-            }
-        }
-        #[expect(clippy::needless_range_loop)]
-        for row in 0..=height {
-            for col in 0..width {
-                hlinks[row][col] = self.vlinks[col][height - row];
-            }
-        }
-        #[expect(clippy::needless_range_loop)]
-        for row in 0..height {
-            for col in 0..=width {
-                vlinks[row][col] = self.hlinks[col][height - 1 - row];
+        )?;
+        // Wildcards and labels are drawn over the loosest values rendered above.
+        if row.is_multiple_of(4) {
+            for col in 0..self.width {
+                if let Some(glyph) = self.hlink_glyph(row / 4, col) {
+                    line[col * 8 + 1..col * 8 + 8].fill(glyph as u8);
+                }
             }
-        }
-        #[expect(clippy::needless_range_loop)]
-        for row in 0..2 * height {
-            for col in 0..2 * width {
-                corners[row][col] = self.corners[col][2 * height - 1 - row];
+        } else {
+            for col in 0..=self.width {
+                if let Some(glyph) = self.vlink_glyph(row / 4, col) {
+                    line[col * 8] = glyph as u8;
+                }
+                if row % 4 != 2 || col == self.width {
+                    continue;
+                }
+                if self.outside[row / 4][col] {
+                    line[col * 8 + 4] = b'#';
+                } else if let Some(glyph) = self.cell_wildcard(row / 4, col) {
+                    line[col * 8 + 4] = glyph as u8;
+                }
             }
         }
+        out.write_all(&line)
+    }
+
+    /// Rotated by a quarter turn, which swaps horizontal and vertical links.
+    pub(super) fn rotated_90(&self) -> HalfRule {
         HalfRule {
-            height,
-            width,
-            cells,
-            hlinks,
-            vlinks,
-            corners,
+            height: self.width,
+            width: self.height,
+            cells: self.cells.rotated_90(),
+            colors: self.colors.rotated_90(),
+            hlinks: self.vlinks.rotated_90(),
+            vlinks: self.hlinks.rotated_90(),
+            corners: self.corners.rotated_90(),
+            cell_patterns: self.cell_patterns.rotated_90(),
+            hlink_patterns: self.vlink_patterns.rotated_90(),
+            vlink_patterns: self.hlink_patterns.rotated_90(),
+            outside: self.outside.rotated_90(),
+            hlink_labels: self.vlink_labels.rotated_90(),
+            vlink_labels: self.hlink_labels.rotated_90(),
+            has_wildcards: self.has_wildcards,
+            has_labels: self.has_labels,
         }
     }
 
     fn reversed_lr(&self) -> HalfRule {
-        let cells = self
-            .cells
-            .iter()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
-        let hlinks = self
-            .hlinks
-            .iter()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
-        let vlinks = self
-            .vlinks
-            .iter()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
-        let corners = self
-            .corners
-            .iter()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
         HalfRule {
             height: self.height,
             width: self.width,
-            cells,
-            hlinks,
-            vlinks,
-            corners,
+            cells: self.cells.reversed_lr(),
+            colors: self.colors.reversed_lr(),
+            hlinks: self.hlinks.reversed_lr(),
+            vlinks: self.vlinks.reversed_lr(),
+            corners: self.corners.reversed_lr(),
+            cell_patterns: self.cell_patterns.reversed_lr(),
+            hlink_patterns: self.hlink_patterns.reversed_lr(),
+            vlink_patterns: self.vlink_patterns.reversed_lr(),
+            outside: self.outside.reversed_lr(),
+            hlink_labels: self.hlink_labels.reversed_lr(),
+            vlink_labels: self.vlink_labels.reversed_lr(),
+            has_wildcards: self.has_wildcards,
+            has_labels: self.has_labels,
         }
     }
+}
 
-    fn reversed_ud(&self) -> HalfRule {
-        let cells = self.cells.iter().rev().cloned().collect();
-        let hlinks = self.hlinks.iter().rev().cloned().collect();
-        let vlinks = self.vlinks.iter().rev().cloned().collect();
-        let corners = self.corners.iter().rev().cloned().collect();
-        HalfRule {
-            height: self.height,
-            width: self.width,
-            cells,
-            hlinks,
-            vlinks,
-            corners,
+/// Symmetries under which the images of a rule are also used, declared with
+/// `[SYMMETRY]` in rule files.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Symmetry {
+    /// Rotations and reflections.
+    #[default]
+    All,
+    /// Rotations only, leaving out mirror images.
+    Rotations,
+    /// The rule as written only.
+    Identity,
+}
+
+impl Symmetry {
+    pub fn parse(name: &str) -> Result<Symmetry, String> {
+        match name {
+            "all" => Ok(Symmetry::All),
+            "rotations-only" => Ok(Symmetry::Rotations),
+            "none" => Ok(Symmetry::Identity),
+            _ => Err(format!(
+                "unknown symmetry {name:?}, expected all, rotations-only or none"
+            )),
         }
     }
 
-    fn rotated_180(&self) -> HalfRule {
-        let cells = self
-            .cells
-            .iter()
-            .rev()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
-        let hlinks = self
-            .hlinks
-            .iter()
-            .rev()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
-        let vlinks = self
-            .vlinks
-            .iter()
-            .rev()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
-        let corners = self
-            .corners
-            .iter()
-            .rev()
-            .map(|row| row.iter().copied().rev().collect())
-            .collect();
-        HalfRule {
-            height: self.height,
-            width: self.width,
-            cells,
-            hlinks,
-            vlinks,
-            corners,
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::All => "all",
+            Symmetry::Rotations => "rotations-only",
+            Symmetry::Identity => "none",
         }
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct Rule {
     name: String,
+    /// Rules of lower priority are cheaper, see [`Game::propagate`].
+    priority: i32,
     rule_in: HalfRule,
     rule_out: HalfRule,
 }
 
 impl Rule {
-    pub fn new(name: &str, rule_str: &str) -> Rule {
-        let mut parts = rule_str.trim().split("=>");
-        let rule_in = HalfRule::new(parts.next().unwrap());
-        let rule_out = HalfRule::new(parts.next().unwrap());
-        assert!(parts.next().is_none());
-        assert_eq!(rule_in.height, rule_out.height);
-        assert_eq!(rule_in.width, rule_out.width);
-        Rule {
+    pub fn new(name: &str, rule_str: &str) -> Result<Rule, String> {
+        let parts = rule_str.trim().split("=>").collect::<Vec<_>>();
+        let [rule_in, rule_out] = parts[..] else {
+            return Err(format!(
+                "rule \"{name}\" should have exactly one \"=>\", found {}",
+                parts.len() - 1
+            ));
+        };
+        let rule_in =
+            HalfRule::new(rule_in).map_err(|e| format!("rule \"{name}\", before \"=>\": {e}"))?;
+        let rule_out =
+            HalfRule::new(rule_out).map_err(|e| format!("rule \"{name}\", after \"=>\": {e}"))?;
+        if (rule_in.height, rule_in.width) != (rule_out.height, rule_out.width) {
+            return Err(format!(
+                "rule \"{name}\" has a {}x{} window before \"=>\" but {}x{} after",
+                rule_in.height, rule_in.width, rule_out.height, rule_out.width
+            ));
+        }
+        if other_wildcard(
+            &rule_out.cells,
+            &rule_out.cell_patterns,
+            &rule_in.cell_patterns,
+        ) || other_wildcard(
+            &rule_out.hlinks,
+            &rule_out.hlink_patterns,
+            &rule_in.hlink_patterns,
+        ) || other_wildcard(
+            &rule_out.vlinks,
+            &rule_out.vlink_patterns,
+            &rule_in.vlink_patterns,
+        ) {
+            return Err(format!(
+                "rule \"{name}\" has a wildcard after \"=>\" that is not repeated from before"
+            ));
+        }
+        if rule_out
+            .outside
+            .rows()
+            .zip(rule_in.outside.rows())
+            .any(|(outs, ins)| outs.iter().zip(ins).any(|(&out, &is_in)| out && !is_in))
+        {
+            return Err(format!(
+                "rule \"{name}\" marks a cell outside the grid after \"=>\" only"
+            ));
+        }
+        Ok(Rule {
             name: name.to_owned(),
+            priority: 0,
             rule_in,
             rule_out,
-        }
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn with_priority(self, priority: i32) -> Rule {
+        Rule { priority, ..self }
     }
 
     pub(super) fn rule_in(&self) -> &HalfRule {
@@ -248,85 +552,307 @@ impl Rule {
         &self.rule_out
     }
 
-    pub fn print(&self) {
-        println!("Rule \"{}\":", self.name);
+    pub fn render(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "Rule \"{}\":", self.name)?;
         let rule_in = &self.rule_in;
         let rule_out = &self.rule_out;
         for row in 0..(4 * rule_in.height + 1) {
-            rule_in.full_print_row(row);
-            print!(
+            rule_in.full_render_row(out, row)?;
+            write!(
+                out,
                 "  {}  ",
                 if row == 2 * rule_in.height {
                     "=>"
                 } else {
                     "  "
                 }
-            );
-            rule_out.full_print_row(row);
-            println!();
+            )?;
+            rule_out.full_render_row(out, row)?;
+            writeln!(out)?;
         }
+        Ok(())
     }
 
-    fn rotated_90(&self) -> Rule {
+    #[allow(dead_code)]
+    pub fn print(&self) {
+        self.render(&mut io::stdout().lock()).unwrap();
+    }
+
+    /// The rule with `f` applied to both of its halves.
+    pub(super) fn map_halves(&self, f: impl Fn(&HalfRule) -> HalfRule) -> Rule {
         Rule {
             name: self.name.clone(),
-            rule_in: self.rule_in.rotated_90(),
-            rule_out: self.rule_out.rotated_90(),
+            priority: self.priority,
+            rule_in: f(&self.rule_in),
+            rule_out: f(&self.rule_out),
         }
     }
 
-    #[allow(dead_code)]
+    pub(super) fn rotated_90(&self) -> Rule {
+        self.map_halves(HalfRule::rotated_90)
+    }
+
     fn reversed_lr(&self) -> Rule {
-        Rule {
-            name: self.name.clone(),
-            rule_in: self.rule_in.reversed_lr(),
-            rule_out: self.rule_out.reversed_lr(),
-        }
+        self.map_halves(HalfRule::reversed_lr)
     }
 
-    fn reversed_ud(&self) -> Rule {
-        Rule {
-            name: self.name.clone(),
-            rule_in: self.rule_in.reversed_ud(),
-            rule_out: self.rule_out.reversed_ud(),
+    /// The distinct images of the rule under `symmetry`, the rule itself
+    /// first. The quarter turns of the rule, and of its mirror image for the
+    /// full dihedral group, give every element of the group; images already
+    /// produced by another element are dropped.
+    pub fn orbit(&self, symmetry: Symmetry) -> Vec<Rule> {
+        self.named_orbit(symmetry)
+            .into_iter()
+            .map(|(_, image)| image)
+            .collect()
+    }
+
+    /// Same as [`Rule::orbit`], with the name of the element of the group
+    /// that produced each image, such as `"mirrored, turned 90"`. Turns are
+    /// counterclockwise.
+    pub fn named_orbit(&self, symmetry: Symmetry) -> Vec<(&'static str, Rule)> {
+        const NAMES: [[&str; 4]; 2] = [
+            ["as written", "turned 90", "turned 180", "turned 270"],
+            [
+                "mirrored",
+                "mirrored, turned 90",
+                "mirrored, turned 180",
+                "mirrored, turned 270",
+            ],
+        ];
+        let (turns, mirrors) = match symmetry {
+            Symmetry::All => (4, vec![self.clone(), self.reversed_lr()]),
+            Symmetry::Rotations => (4, vec![self.clone()]),
+            Symmetry::Identity => (1, vec![self.clone()]),
+        };
+        let mut seen = HashSet::new();
+        let mut orbit = Vec::new();
+        for (names, mut image) in NAMES.iter().zip(mirrors) {
+            for &name in &names[..turns] {
+                let next = image.rotated_90();
+                if seen.insert(image.clone()) {
+                    orbit.push((name, image));
+                }
+                image = next;
+            }
         }
+        orbit
     }
+}
 
-    fn rotated_180(&self) -> Rule {
-        Rule {
-            name: self.name.clone(),
-            rule_in: self.rule_in.rotated_180(),
-            rule_out: self.rule_out.rotated_180(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines of a window of `height` by `width` cells without clues or
+    /// decided links.
+    fn blank_window(height: usize, width: usize) -> Vec<String> {
+        let border = format!("+{}", ".......+".repeat(width));
+        let inner = format!(".{}", "       .".repeat(width));
+        let mut lines = vec![border.clone()];
+        for _ in 0..height {
+            lines.extend([inner.clone(), inner.clone(), inner.clone(), border.clone()]);
         }
+        lines
+    }
+
+    /// Rule whose window is `window` before and after `=>`.
+    fn unchanged(name: &str, window: &[String]) -> Rule {
+        let window = window.join("\n");
+        Rule::new(name, &format!("{window}\n=>\n{window}")).unwrap()
+    }
+
+    fn set_edge(half: &mut HalfRule, edge: Edge, link: LinkType) {
+        let (links, patterns) = match edge {
+            Edge::H(..) => (&mut half.hlinks, &mut half.hlink_patterns),
+            Edge::V(..) => (&mut half.vlinks, &mut half.vlink_patterns),
+        };
+        let (Edge::H(row, col) | Edge::V(row, col)) = edge;
+        links[row][col] = link;
+        patterns[row][col] = Pattern::refining(link);
     }
 
-    pub fn symmetries(&self) -> Vec<Rule> {
-        let r180 = self.rotated_180();
-        if *self != r180 {
-            let r90 = self.rotated_90();
-            let r270 = r90.rotated_180();
-            let refl = self.reversed_ud();
-            if refl == *self || refl == r90 || refl == r180 || refl == r270 {
-                return vec![r180, r90, r270];
+    /// Rule on a window of `size` cells with the clues 1 at `one` and 2 at
+    /// `two`, which finds `link` linked, and decides that `unlink` is unlinked
+    /// and that the `corner` has one link.
+    fn domino(
+        size: (usize, usize),
+        one: (usize, usize),
+        two: (usize, usize),
+        link: Edge,
+        unlink: Edge,
+        corner: (usize, usize),
+    ) -> Rule {
+        let mut rule = unchanged("Domino", &blank_window(size.0, size.1));
+        for half in [&mut rule.rule_in, &mut rule.rule_out] {
+            for (cell, clue) in [(one, One), (two, Two)] {
+                half.cells[cell.0][cell.1] = clue;
+                half.cell_patterns[cell.0][cell.1] = Pattern::refining(clue);
             }
-            let refl2 = r90.reversed_ud();
-            let refl3 = r180.reversed_ud();
-            let refl4 = r270.reversed_ud();
-            return vec![r90, r180, r270, refl, refl2, refl3, refl4];
+            set_edge(half, link, Link);
         }
-        let r90 = self.rotated_90();
-        if *self != r90 {
-            let refl = self.reversed_ud();
-            if *self != refl {
-                let refl2 = r90.reversed_ud();
-                if *self != refl2 {
-                    return vec![r90, refl, refl2];
-                }
-                return vec![r90];
-            }
-            return vec![r90];
+        set_edge(&mut rule.rule_out, unlink, Unlink);
+        rule.rule_out.corners[corner.0][corner.1] = COne;
+        rule
+    }
+
+    /// The images of the domino with its 1 left of its 2 and linked on top,
+    /// in the order of [`Rule::orbit`]: its quarter turns counterclockwise,
+    /// then those of its mirror image.
+    fn domino_images() -> Vec<Rule> {
+        use Edge::{H, V};
+        vec![
+            domino((1, 2), (0, 0), (0, 1), H(0, 0), H(1, 0), (0, 0)),
+            domino((2, 1), (1, 0), (0, 0), V(1, 0), V(1, 1), (3, 0)),
+            domino((1, 2), (0, 1), (0, 0), H(1, 1), H(0, 1), (1, 3)),
+            domino((2, 1), (0, 0), (1, 0), V(0, 1), V(0, 0), (0, 1)),
+            domino((1, 2), (0, 1), (0, 0), H(0, 1), H(1, 1), (0, 3)),
+            domino((2, 1), (0, 0), (1, 0), V(0, 0), V(0, 1), (0, 0)),
+            domino((1, 2), (0, 0), (0, 1), H(1, 0), H(0, 0), (1, 0)),
+            domino((2, 1), (1, 0), (0, 0), V(1, 1), V(1, 0), (3, 1)),
+        ]
+    }
+
+    #[test]
+    fn orbits_of_an_asymmetric_rule_are_the_hand_computed_images() {
+        let images = domino_images();
+        let rule = &images[0];
+        assert_eq!(rule.orbit(Symmetry::All), images);
+        assert_eq!(rule.orbit(Symmetry::Rotations), images[..4]);
+        assert_eq!(rule.orbit(Symmetry::Identity), images[..1]);
+    }
+
+    #[test]
+    fn orbits_drop_repeated_images() {
+        let zero = Rule::new(
+            "Auto solving zero",
+            "+.......+\n.       .\n.   0   .\n.       .\n+.......+\n=>\n\
+             +       +\n         \n    0    \n         \n+       +",
+        )
+        .unwrap();
+        assert_eq!(zero.orbit(Symmetry::All).len(), 1);
+        // A line through the middle of a square window.
+        let mut window = blank_window(2, 2);
+        window[4] = "+=======+=======+".to_owned();
+        let straight = unchanged("Straight", &window);
+        let orbit = straight.orbit(Symmetry::All);
+        assert_eq!(orbit, [straight.clone(), straight.rotated_90()]);
+        assert_eq!(straight.orbit(Symmetry::Rotations), orbit);
+        let names: Vec<_> = straight
+            .named_orbit(Symmetry::All)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["as written", "turned 90"]);
+    }
+
+    #[test]
+    fn rotations_and_reflections_round_trip() {
+        let rule = &domino_images()[0];
+        let turned = (0..4).fold(rule.clone(), |image, _| image.rotated_90());
+        assert_eq!(&turned, rule);
+        assert_ne!(&rule.reversed_lr(), rule);
+        assert_eq!(&rule.reversed_lr().reversed_lr(), rule);
+    }
+
+    /// Window of a single cell with the glyphs `cell`, `top` for its top link
+    /// and `left` for its left link.
+    fn cell_window(cell: char, top: char, left: char) -> String {
+        format!("+...{top}...+\n{left}       .\n{left}   {cell}   .\n{left}       .\n+.......+")
+    }
+
+    #[test]
+    fn wildcard_glyphs_parse_into_their_patterns() {
+        for (glyph, values) in CELL_WILDCARDS {
+            let half = HalfRule::new(&cell_window(glyph, '.', '.')).unwrap();
+            assert_eq!(half.cell_patterns[0][0], Pattern::of(values), "{glyph}");
+            assert!(half.has_wildcards);
+        }
+        for (glyph, values) in HLINK_WILDCARDS {
+            let half = HalfRule::new(&cell_window(' ', glyph, '.')).unwrap();
+            assert_eq!(half.hlink_patterns[0][0], Pattern::of(values), "{glyph}");
+        }
+        for (glyph, values) in VLINK_WILDCARDS {
+            let half = HalfRule::new(&cell_window(' ', '.', glyph)).unwrap();
+            assert_eq!(half.vlink_patterns[0][0], Pattern::of(values), "{glyph}");
+        }
+        let half = HalfRule::new(&cell_window('2', '=', ' ')).unwrap();
+        assert_eq!(half.cell_patterns[0][0], Pattern::refining(Two));
+        assert_eq!(half.hlink_patterns[0][0], Pattern::refining(Link));
+        assert_eq!(half.vlink_patterns[0][0], Pattern::refining(Unlink));
+        assert!(!half.has_wildcards);
+    }
+
+    /// A blank 5x5 board but for `clue` in its cell `(2, 2)`, counting the
+    /// padding.
+    fn game_with_clue(clue: char) -> Game {
+        crate::puzzle::parse_puzzle(&format!("...../.{clue}.../...../...../.....")).unwrap()
+    }
+
+    #[test]
+    fn wildcards_match_the_values_they_stand_for() {
+        // A clue 2 or 3 whose top link is linked or undecided, and whose
+        // left link is unlinked or undecided.
+        let window = cell_window('h', '-', ',');
+        let rule = Rule::new("Wildcards", &format!("{window}\n=>\n{window}")).unwrap();
+        for (clue, matches) in [('2', true), ('3', true), ('1', false), ('.', false)] {
+            let mut game = game_with_clue(clue);
+            assert_eq!(
+                rule.rule_in().wildcards_match(&game, 2, 2),
+                matches,
+                "{clue}"
+            );
+            assert_eq!(
+                game.try_apply_rule(&rule, 2, 2).unwrap().is_some(),
+                matches,
+                "{clue}"
+            );
+        }
+        let mut game = game_with_clue('2');
+        game.set_hlink(2, 2, Link);
+        game.set_vlink(2, 2, Unlink);
+        assert!(rule.rule_in().wildcards_match(&game, 2, 2));
+        let mut game = game_with_clue('2');
+        game.set_hlink(2, 2, Unlink);
+        assert!(!rule.rule_in().wildcards_match(&game, 2, 2));
+        assert_eq!(game.try_apply_rule(&rule, 2, 2), Ok(None));
+        let mut game = game_with_clue('2');
+        game.set_vlink(2, 2, Link);
+        assert!(!rule.rule_in().wildcards_match(&game, 2, 2));
+    }
+
+    #[test]
+    fn wildcards_after_the_arrow_repeat_those_before() {
+        let before = cell_window('h', '.', '.');
+        let e = Rule::new(
+            "Wildcards",
+            &format!("{before}\n=>\n{}", cell_window('n', '.', '.')),
+        )
+        .unwrap_err();
+        assert!(
+            e.contains("wildcard after \"=>\" that is not repeated"),
+            "{e}"
+        );
+        let e = Rule::new(
+            "Wildcards",
+            &format!("{before}\n=>\n{}", cell_window('h', '-', '.')),
+        )
+        .unwrap_err();
+        assert!(
+            e.contains("wildcard after \"=>\" that is not repeated"),
+            "{e}"
+        );
+        assert!(Rule::new("Wildcards", &format!("{before}\n=>\n{before}")).is_ok());
+    }
+
+    #[test]
+    fn empty_windows_are_rejected() {
+        for window in [
+            "+\n \n \n \n+\n=>\n+\n \n \n \n+",
+            "+.......+\n=>\n+.......+",
+        ] {
+            let e = Rule::new("empty", window).unwrap_err();
+            assert!(e.contains("should have cells"), "{e}");
         }
-        let refl = self.reversed_ud();
-        if *self == refl { vec![] } else { vec![refl] }
     }
 }
@@ -0,0 +1,166 @@
+use super::element::*;
+use super::vertex::Edge;
+use super::{ClueStatus, Contradiction, Game, Rule};
+
+/// Result of [`Game::search`].
+pub struct SearchOutcome {
+    pub solutions: Vec<Game>,
+    /// Number of guesses made on undecided links.
+    pub guesses: usize,
+}
+
+impl Game {
+    fn undecided_edge(&self) -> Option<Edge> {
+        for row in 0..=self.height {
+            for col in 0..=self.width {
+                if self.vertex_degree(row, col).0 == 1
+                    && let Some((edge, _)) = self
+                        .vertex_edges(row, col)
+                        .find(|&(_, link)| link == LMaybe)
+                {
+                    return Some(edge);
+                }
+            }
+        }
+        for row in 0..=self.height {
+            for col in 0..self.width {
+                if self.hlinks[row][col] == LMaybe {
+                    return Some(Edge::H(row, col));
+                }
+            }
+        }
+        for row in 0..self.height {
+            for col in 0..=self.width {
+                if self.vlinks[row][col] == LMaybe {
+                    return Some(Edge::V(row, col));
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether every link is decided.
+    pub fn is_complete(&self) -> bool {
+        self.hlinks
+            .rows()
+            .chain(self.vlinks.rows())
+            .all(|row| row.iter().all(|&link| link != LMaybe))
+    }
+
+    /// Number of decided links and total number of links of the puzzle,
+    /// leaving out the padding.
+    pub fn decided_links(&self) -> (usize, usize) {
+        let hlinks = (1..self.height).flat_map(|row| &self.hlinks[row][1..self.width - 1]);
+        let vlinks = (1..self.height - 1).flat_map(|row| &self.vlinks[row][1..self.width]);
+        hlinks
+            .chain(vlinks)
+            .fold((0, 0), |(decided, total), &link| {
+                (decided + (link != LMaybe) as usize, total + 1)
+            })
+    }
+
+    /// Checks the board for violated clues, vertices that cannot have degree
+    /// 0 or 2 any more, and closed loops that leave other links out.
+    pub fn check(&self) -> Result<(), Contradiction> {
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if self.clue_status(row, col) == ClueStatus::Violated {
+                    return Err(Contradiction);
+                }
+            }
+        }
+        let vertices_width = self.width + 1;
+        let mut parent = (0..(self.height + 1) * vertices_width).collect::<Vec<_>>();
+        fn find(parent: &mut [usize], mut v: usize) -> usize {
+            while parent[v] != v {
+                parent[v] = parent[parent[v]];
+                v = parent[v];
+            }
+            v
+        }
+        for row in 0..=self.height {
+            for col in 0..=self.width {
+                let (links, maybes) = self.vertex_degree(row, col);
+                if links > 2 || (links == 1 && maybes == 0) {
+                    return Err(Contradiction);
+                }
+                let v = row * vertices_width + col;
+                if col < self.width && self.hlinks[row][col] == Link {
+                    let (a, b) = (find(&mut parent, v), find(&mut parent, v + 1));
+                    parent[a] = b;
+                }
+                if row < self.height && self.vlinks[row][col] == Link {
+                    let (a, b) = (find(&mut parent, v), find(&mut parent, v + vertices_width));
+                    parent[a] = b;
+                }
+            }
+        }
+        // A component is a closed loop if none of its vertices is a loose end.
+        let mut components = Vec::new();
+        let mut open = Vec::new();
+        for row in 0..=self.height {
+            for col in 0..=self.width {
+                let (links, _) = self.vertex_degree(row, col);
+                if links == 0 {
+                    continue;
+                }
+                let root = find(&mut parent, row * vertices_width + col);
+                if !components.contains(&root) {
+                    components.push(root);
+                }
+                if links == 1 && !open.contains(&root) {
+                    open.push(root);
+                }
+            }
+        }
+        if components.len() > 1 && open.len() < components.len() {
+            return Err(Contradiction);
+        }
+        Ok(())
+    }
+
+    /// Whether the board is complete and its links form a single loop
+    /// satisfying every clue.
+    pub fn is_solved(&self) -> bool {
+        self.is_complete()
+            && self.check().is_ok()
+            && self.hlinks.rows().any(|row| row.contains(&Link))
+    }
+
+    /// Searches for up to `limit` solutions, propagating `rules` and guessing
+    /// undecided links when they get stuck. The board is left unchanged.
+    pub fn search(&mut self, rules: &[Rule], limit: usize) -> SearchOutcome {
+        let mut outcome = SearchOutcome {
+            solutions: Vec::new(),
+            guesses: 0,
+        };
+        self.search_from(rules, limit, &mut outcome);
+        outcome
+    }
+
+    fn search_from(&mut self, rules: &[Rule], limit: usize, outcome: &mut SearchOutcome) {
+        let checkpoint = self.checkpoint();
+        if self.propagate(rules).is_ok() && self.check().is_ok() {
+            match self.undecided_edge() {
+                None => {
+                    if self.is_solved() {
+                        outcome.solutions.push(self.clone());
+                    }
+                }
+                Some(edge) => {
+                    for link in [Link, Unlink] {
+                        if outcome.solutions.len() >= limit {
+                            break;
+                        }
+                        outcome.guesses += 1;
+                        let guess = self.checkpoint();
+                        self.write_edge(edge, link);
+                        self.search_from(rules, limit, outcome);
+                        self.rollback(guess);
+                    }
+                }
+            }
+        }
+        self.rollback(checkpoint);
+    }
+}
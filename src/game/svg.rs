@@ -0,0 +1,281 @@
+use super::element::*;
+use super::grid::Grid;
+use super::rule::HalfRule;
+use super::{Game, Rule};
+use std::fmt::Write;
+
+const CELL: usize = 40;
+const MARGIN: usize = 20;
+const ARROW: usize = 60;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[expect(clippy::too_many_arguments)]
+fn write_panel(
+    svg: &mut String,
+    x0: usize,
+    y0: usize,
+    height: usize,
+    width: usize,
+    cells: &Grid<CellType>,
+    colors: &Grid<ColorType>,
+    hlinks: &Grid<LinkType>,
+    vlinks: &Grid<LinkType>,
+    corners: &Grid<CornerType>,
+    trim: bool,
+    show_corners: bool,
+) {
+    let (row_l, row_r) = if trim { (1, height - 1) } else { (0, height) };
+    let (col_l, col_r) = if trim { (1, width - 1) } else { (0, width) };
+    let x = |col: usize| x0 + (col - col_l) * CELL;
+    let y = |row: usize| y0 + (row - row_l) * CELL;
+    let cross = |svg: &mut String, cx: usize, cy: usize| {
+        writeln!(
+            svg,
+            r#"<path d="M{} {}l8 8m0 -8l-8 8" stroke="gray" stroke-width="1.5"/>"#,
+            cx - 4,
+            cy - 4
+        )
+        .unwrap();
+    };
+    for row in row_l..row_r {
+        for col in col_l..col_r {
+            let fill = match colors[row][col] {
+                Inside => "lemonchiffon",
+                Outside => "gainsboro",
+                Unknown => continue,
+            };
+            writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{CELL}" height="{CELL}" fill="{fill}"/>"#,
+                x(col),
+                y(row)
+            )
+            .unwrap();
+        }
+    }
+    for row in row_l..=row_r {
+        for col in col_l..col_r {
+            match hlinks[row][col] {
+                Link => writeln!(
+                    svg,
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="black" stroke-width="4" stroke-linecap="round"/>"#,
+                    x(col),
+                    y(row),
+                    x(col + 1),
+                    y(row)
+                )
+                .unwrap(),
+                Unlink => cross(svg, x(col) + CELL / 2, y(row)),
+                LMaybe => (),
+            }
+        }
+    }
+    for row in row_l..row_r {
+        for col in col_l..=col_r {
+            match vlinks[row][col] {
+                Link => writeln!(
+                    svg,
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="black" stroke-width="4" stroke-linecap="round"/>"#,
+                    x(col),
+                    y(row),
+                    x(col),
+                    y(row + 1)
+                )
+                .unwrap(),
+                Unlink => cross(svg, x(col), y(row) + CELL / 2),
+                LMaybe => (),
+            }
+        }
+    }
+    for row in row_l..=row_r {
+        for col in col_l..=col_r {
+            writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="2.5" fill="black"/>"#,
+                x(col),
+                y(row)
+            )
+            .unwrap();
+        }
+    }
+    for row in row_l..row_r {
+        for col in col_l..col_r {
+            if cells[row][col] != Empty {
+                writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="22" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                    x(col) + CELL / 2,
+                    y(row) + CELL / 2,
+                    cells[row][col].to_char()
+                )
+                .unwrap();
+            }
+            if !show_corners {
+                continue;
+            }
+            for (dr, dc) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                let corner = corners[2 * row + dr][2 * col + dc];
+                if corner == CMaybe {
+                    continue;
+                }
+                writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="9" fill="royalblue" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                    x(col) + if dc == 0 { 8 } else { CELL - 8 },
+                    y(row) + if dr == 0 { 8 } else { CELL - 8 },
+                    corner.to_char()
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+/// Labels the wildcards and labelled links of a rule half drawn by
+/// [`write_panel`] at `(x0, y0)` with their glyphs, on top of the loosest
+/// values drawn there, and hatches its cells outside the grid.
+fn write_wildcards(svg: &mut String, x0: usize, y0: usize, half: &HalfRule) {
+    for row in 0..half.height {
+        for col in 0..half.width {
+            if half.outside[row][col] {
+                writeln!(
+                    svg,
+                    r#"<path d="M{} {}l{CELL} {CELL}m0 -{CELL}l-{CELL} {CELL}" stroke="silver" stroke-width="1"/>"#,
+                    x0 + col * CELL,
+                    y0 + row * CELL
+                )
+                .unwrap();
+            }
+        }
+    }
+    let mut label = |x: usize, y: usize, glyph: char| {
+        writeln!(
+            svg,
+            r#"<text x="{x}" y="{y}" font-size="18" fill="gray" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+            escape(&glyph.to_string())
+        )
+        .unwrap();
+    };
+    for row in 0..half.height {
+        for col in 0..half.width {
+            if let Some(glyph) = half.cell_wildcard(row, col) {
+                label(
+                    x0 + col * CELL + CELL / 2,
+                    y0 + row * CELL + CELL / 2,
+                    glyph,
+                );
+            }
+        }
+        for col in 0..=half.width {
+            if let Some(glyph) = half.vlink_glyph(row, col) {
+                label(x0 + col * CELL, y0 + row * CELL + CELL / 2, glyph);
+            }
+        }
+    }
+    for row in 0..=half.height {
+        for col in 0..half.width {
+            if let Some(glyph) = half.hlink_glyph(row, col) {
+                label(x0 + col * CELL + CELL / 2, y0 + row * CELL, glyph);
+            }
+        }
+    }
+}
+
+fn open_svg(svg: &mut String, width: usize, height: usize) {
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif">"#
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect width="{width}" height="{height}" fill="white"/>"#
+    )
+    .unwrap();
+}
+
+impl Game {
+    /// Renders the board (without its padding) as a standalone SVG document.
+    pub fn to_svg(&self, show_corners: bool) -> String {
+        let mut svg = String::new();
+        open_svg(
+            &mut svg,
+            2 * MARGIN + (self.width - 2) * CELL,
+            2 * MARGIN + (self.height - 2) * CELL,
+        );
+        write_panel(
+            &mut svg,
+            MARGIN,
+            MARGIN,
+            self.height,
+            self.width,
+            &self.cells,
+            &self.colors,
+            &self.hlinks,
+            &self.vlinks,
+            &self.corners,
+            true,
+            show_corners,
+        );
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl Rule {
+    /// Renders the rule as a standalone SVG document, with the `rule_in`
+    /// and `rule_out` panels side by side.
+    pub fn to_svg(&self) -> String {
+        let rule_in = self.rule_in();
+        let rule_out = self.rule_out();
+        let panel_width = rule_in.width * CELL;
+        let title_height = 30;
+        let mut svg = String::new();
+        open_svg(
+            &mut svg,
+            2 * MARGIN + 2 * panel_width + ARROW,
+            2 * MARGIN + title_height + rule_in.height * CELL,
+        );
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="16">{}</text>"#,
+            MARGIN,
+            MARGIN + 10,
+            escape(self.name())
+        )
+        .unwrap();
+        let y0 = MARGIN + title_height;
+        for (x0, half) in [(MARGIN, rule_in), (MARGIN + panel_width + ARROW, rule_out)] {
+            write_panel(
+                &mut svg,
+                x0,
+                y0,
+                half.height,
+                half.width,
+                &half.cells,
+                &half.colors,
+                &half.hlinks,
+                &half.vlinks,
+                &half.corners,
+                false,
+                true,
+            );
+            write_wildcards(&mut svg, x0, y0, half);
+        }
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="24" text-anchor="middle" dominant-baseline="central">&#8658;</text>"#,
+            MARGIN + panel_width + ARROW / 2,
+            y0 + rule_in.height * CELL / 2
+        )
+        .unwrap();
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
@@ -0,0 +1,155 @@
+use super::Game;
+use super::element::*;
+use std::io::{self, Write};
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[32m";
+const HIGHLIGHT: &str = "\x1b[43m";
+const CURSOR: &str = "\x1b[7m";
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ClueStatus {
+    NoClue,
+    Open,
+    Satisfied,
+    Violated,
+}
+
+fn vertex_glyph(up: bool, down: bool, left: bool, right: bool) -> char {
+    match (up, down, left, right) {
+        (false, false, false, false) => '·',
+        (true, false, false, false) => '╵',
+        (false, true, false, false) => '╷',
+        (false, false, true, false) => '╴',
+        (false, false, false, true) => '╶',
+        (true, true, false, false) => '│',
+        (false, false, true, true) => '─',
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
+}
+
+impl Game {
+    /// Compares the clue of a cell with the links currently around it.
+    pub fn clue_status(&self, row: usize, col: usize) -> ClueStatus {
+        let clue = match self.cells[row][col] {
+            Empty => return ClueStatus::NoClue,
+            Zero => 0,
+            One => 1,
+            Two => 2,
+            Three => 3,
+        };
+        let sides = [
+            self.hlinks[row][col],
+            self.hlinks[row + 1][col],
+            self.vlinks[row][col],
+            self.vlinks[row][col + 1],
+        ];
+        let links = sides.iter().filter(|&&link| link == Link).count();
+        let maybes = sides.iter().filter(|&&link| link == LMaybe).count();
+        if links > clue || links + maybes < clue {
+            ClueStatus::Violated
+        } else if maybes == 0 {
+            ClueStatus::Satisfied
+        } else {
+            ClueStatus::Open
+        }
+    }
+
+    /// Renders the board with box-drawing characters. With `color`, clues are
+    /// colored by their [`ClueStatus`], unlinks are dimmed and the cells of the
+    /// last modifying rule application are highlighted. The cell under
+    /// `cursor`, if any, is shown in reverse video, or bracketed without
+    /// `color`.
+    pub fn render_unicode<W: Write>(
+        &self,
+        out: &mut W,
+        color: bool,
+        cursor: Option<(usize, usize)>,
+    ) -> io::Result<()> {
+        let paint = |out: &mut W, style: &str, text: &str| {
+            if color && !style.is_empty() {
+                write!(out, "{style}{text}{RESET}")
+            } else {
+                write!(out, "{text}")
+            }
+        };
+        let highlighted = |row: usize, col: usize| match self.last_applied {
+            Some((row_s, col_s, r_height, r_width)) => {
+                (row_s..row_s + r_height).contains(&row) && (col_s..col_s + r_width).contains(&col)
+            }
+            None => false,
+        };
+        for row in 1..self.height {
+            write!(out, " ")?;
+            for col in 1..self.width {
+                let glyph = vertex_glyph(
+                    self.vlinks[row - 1][col] == Link,
+                    self.vlinks[row][col] == Link,
+                    self.hlinks[row][col - 1] == Link,
+                    self.hlinks[row][col] == Link,
+                );
+                write!(out, "{glyph}")?;
+                if col == self.width - 1 {
+                    break;
+                }
+                match self.hlinks[row][col] {
+                    Link => write!(out, "───")?,
+                    Unlink => paint(out, DIM, " × ")?,
+                    LMaybe => write!(out, "   ")?,
+                }
+            }
+            writeln!(out)?;
+            if row == self.height - 1 {
+                break;
+            }
+            write!(out, " ")?;
+            for col in 1..self.width {
+                match self.vlinks[row][col] {
+                    Link => write!(out, "│")?,
+                    Unlink => paint(out, DIM, "×")?,
+                    LMaybe => write!(out, " ")?,
+                }
+                if col == self.width - 1 {
+                    break;
+                }
+                let clue = self.cells[row][col].to_char();
+                let mut style = match self.clue_status(row, col) {
+                    ClueStatus::Satisfied => GREEN,
+                    ClueStatus::Violated => RED,
+                    ClueStatus::NoClue | ClueStatus::Open => "",
+                }
+                .to_owned();
+                if highlighted(row, col) {
+                    style.insert_str(0, HIGHLIGHT);
+                }
+                if cursor == Some((row, col)) {
+                    if color {
+                        style.insert_str(0, CURSOR);
+                    } else {
+                        write!(out, "[{clue}]")?;
+                        continue;
+                    }
+                }
+                paint(out, &style, &format!(" {clue} "))?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn print_unicode(&self) {
+        self.render_unicode(&mut io::stdout().lock(), true, None)
+            .unwrap();
+    }
+}
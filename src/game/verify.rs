@@ -0,0 +1,222 @@
+use super::Rule;
+use super::element::*;
+use super::rule::HalfRule;
+use super::vertex::Edge;
+
+const MAX_FREE_LINKS: usize = 24;
+
+fn link_of(linked: bool) -> LinkType {
+    if linked { Link } else { Unlink }
+}
+
+fn corner_of(count: usize) -> CornerType {
+    [CZero, COne, CTwo][count]
+}
+
+/// Letter of the first label of `half` whose links are not related as
+/// labelled under the assignment `hlinks`, `vlinks`.
+fn broken_label(half: &HalfRule, hlinks: &[Vec<bool>], vlinks: &[Vec<bool>]) -> Option<char> {
+    let value = |edge: Edge| match edge {
+        Edge::H(row, col) => hlinks[row][col],
+        Edge::V(row, col) => vlinks[row][col],
+    };
+    let links = half.labelled_links();
+    links
+        .iter()
+        .find(|&&(edge, label)| {
+            let &(first, first_label) = links
+                .iter()
+                .find(|(_, other)| other.letter == label.letter)
+                .unwrap();
+            value(edge) ^ label.opposite != value(first) ^ first_label.opposite
+        })
+        .map(|(_, label)| label.letter as char)
+}
+
+impl Rule {
+    /// Checks by exhaustion that every configuration of links allowed by
+    /// `rule_in`, under any of the clues its wildcards match, also satisfies
+    /// `rule_out`. Only the clues, corners and vertex degrees inside the
+    /// window are taken into account, so rules relying on the loop being
+    /// single are reported as unsound. Link wildcards are verified like
+    /// undecided links, since an undecided link matches them, and cells
+    /// outside the grid as cells of the padding. Labels in `rule_in` restrict
+    /// the configurations, and those in `rule_out` must hold in all of them.
+    pub fn verify(&self) -> Result<(), String> {
+        let rule_in = self.rule_in();
+        let rule_out = self.rule_out();
+        let (height, width) = (rule_in.height, rule_in.width);
+        if rule_in.cells != rule_out.cells {
+            return Err("changes clues, which cannot be verified".to_owned());
+        }
+        // Links between two cells outside the grid belong to the padding, so
+        // they are unlinked whatever the rule says.
+        let outside = |row: usize, col: usize| rule_in.outside[row][col];
+        let mut hlinks = vec![vec![false; width]; height + 1];
+        let mut vlinks = vec![vec![false; width + 1]; height];
+        let mut free = Vec::new();
+        #[expect(clippy::needless_range_loop)]
+        for row in 0..=height {
+            for col in 0..width {
+                let padding = row > 0 && row < height && outside(row - 1, col) && outside(row, col);
+                match rule_in.hlinks[row][col] {
+                    LMaybe if !padding => free.push((true, row, col)),
+                    link => hlinks[row][col] = link == Link,
+                }
+            }
+        }
+        #[expect(clippy::needless_range_loop)]
+        for row in 0..height {
+            for col in 0..=width {
+                let padding = col > 0 && col < width && outside(row, col - 1) && outside(row, col);
+                match rule_in.vlinks[row][col] {
+                    LMaybe if !padding => free.push((false, row, col)),
+                    link => vlinks[row][col] = link == Link,
+                }
+            }
+        }
+        if free.len() > MAX_FREE_LINKS {
+            return Err(format!(
+                "has {} undecided links, at most {MAX_FREE_LINKS} can be verified",
+                free.len()
+            ));
+        }
+        for assignment in 0u32..1 << free.len() {
+            for (bit, &(horizontal, row, col)) in free.iter().enumerate() {
+                let linked = assignment >> bit & 1 == 1;
+                if horizontal {
+                    hlinks[row][col] = linked;
+                } else {
+                    vlinks[row][col] = linked;
+                }
+            }
+            let corner = |row: usize, col: usize| {
+                let (cell_row, cell_col) = (row / 2, col / 2);
+                let count = hlinks[cell_row + row % 2][cell_col] as usize
+                    + vlinks[cell_row][cell_col + col % 2] as usize;
+                corner_of(count)
+            };
+            let possible = (0..height).all(|row| {
+                (0..width).all(|col| {
+                    let count = hlinks[row][col] as usize
+                        + hlinks[row + 1][col] as usize
+                        + vlinks[row][col] as usize
+                        + vlinks[row][col + 1] as usize;
+                    let pattern = rule_in.cell_patterns[row][col];
+                    pattern.matches(Empty)
+                        || [Zero, One, Two, Three]
+                            .get(count)
+                            .is_some_and(|&clue| pattern.matches(clue))
+                })
+            }) && (0..2 * height)
+                .all(|row| (0..2 * width).all(|col| rule_in.corners[row][col] >= corner(row, col)))
+                && (0..=height).all(|row| {
+                    (0..=width).all(|col| {
+                        let degree = (row > 0 && vlinks[row - 1][col]) as usize
+                            + (row < height && vlinks[row][col]) as usize
+                            + (col > 0 && hlinks[row][col - 1]) as usize
+                            + (col < width && hlinks[row][col]) as usize;
+                        let interior = row > 0 && row < height && col > 0 && col < width;
+                        if interior {
+                            degree == 0 || degree == 2
+                        } else {
+                            degree <= 2
+                        }
+                    })
+                })
+                && broken_label(rule_in, &hlinks, &vlinks).is_none();
+            if !possible {
+                continue;
+            }
+            // Colours follow from the links up to a flip, taking each link
+            // crossed from the top left cell as a change of colour.
+            let colorings = [Inside, Outside]
+                .into_iter()
+                .map(|first| {
+                    let mut colors = vec![vec![first; width]; height];
+                    for row in 0..height {
+                        for col in 0..width {
+                            let crossed = if col > 0 {
+                                (colors[row][col - 1], vlinks[row][col])
+                            } else if row > 0 {
+                                (colors[row - 1][col], hlinks[row][col])
+                            } else {
+                                continue;
+                            };
+                            colors[row][col] = match crossed {
+                                (color, false) => color,
+                                (Inside, true) => Outside,
+                                (_, true) => Inside,
+                            };
+                        }
+                    }
+                    colors
+                })
+                .filter(|colors| {
+                    (0..height).all(|row| {
+                        (0..width).all(|col| {
+                            rule_in.colors[row][col] >= colors[row][col]
+                                && (!outside(row, col) || colors[row][col] == Outside)
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            if colorings.is_empty() {
+                continue;
+            }
+            if let Some(letter) = broken_label(rule_out, &hlinks, &vlinks) {
+                return Err(format!(
+                    "links labelled {letter:?} can break their relation after matching"
+                ));
+            }
+            for colors in &colorings {
+                for (row, colors) in colors.iter().enumerate() {
+                    for (col, &color) in colors.iter().enumerate() {
+                        #[expect(clippy::neg_cmp_op_on_partial_ord)]
+                        if !(rule_out.colors[row][col] >= color) {
+                            return Err(format!(
+                                "colour of cell ({row}, {col}) can be {color:?} after matching"
+                            ));
+                        }
+                    }
+                }
+            }
+            #[expect(clippy::needless_range_loop)]
+            for row in 0..=height {
+                for col in 0..width {
+                    let link = link_of(hlinks[row][col]);
+                    #[expect(clippy::neg_cmp_op_on_partial_ord)]
+                    if !(rule_out.hlinks[row][col] >= link) {
+                        return Err(format!(
+                            "horizontal link ({row}, {col}) can be {link:?} after matching"
+                        ));
+                    }
+                }
+            }
+            #[expect(clippy::needless_range_loop)]
+            for row in 0..height {
+                for col in 0..=width {
+                    let link = link_of(vlinks[row][col]);
+                    #[expect(clippy::neg_cmp_op_on_partial_ord)]
+                    if !(rule_out.vlinks[row][col] >= link) {
+                        return Err(format!(
+                            "vertical link ({row}, {col}) can be {link:?} after matching"
+                        ));
+                    }
+                }
+            }
+            for row in 0..2 * height {
+                for col in 0..2 * width {
+                    let corner = corner(row, col);
+                    #[expect(clippy::neg_cmp_op_on_partial_ord)]
+                    if !(rule_out.corners[row][col] >= corner) {
+                        return Err(format!(
+                            "corner ({row}, {col}) can be {corner:?} after matching"
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
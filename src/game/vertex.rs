@@ -0,0 +1,81 @@
+use super::element::*;
+use super::{Contradiction, Game};
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub(super) enum Edge {
+    H(usize, usize),
+    V(usize, usize),
+}
+
+impl Game {
+    /// Links incident to the vertex at `(row, col)`, as `(edge, state)` pairs.
+    pub(super) fn vertex_edges(
+        &self,
+        row: usize,
+        col: usize,
+    ) -> impl Iterator<Item = (Edge, LinkType)> + '_ {
+        let up = (row > 0).then(|| (Edge::V(row - 1, col), self.vlinks[row - 1][col]));
+        let down = (row < self.height).then(|| (Edge::V(row, col), self.vlinks[row][col]));
+        let left = (col > 0).then(|| (Edge::H(row, col - 1), self.hlinks[row][col - 1]));
+        let right = (col < self.width).then(|| (Edge::H(row, col), self.hlinks[row][col]));
+        [up, down, left, right].into_iter().flatten()
+    }
+
+    /// Number of `Link` and `LMaybe` edges at the vertex at `(row, col)`.
+    pub(super) fn vertex_degree(&self, row: usize, col: usize) -> (usize, usize) {
+        self.vertex_edges(row, col)
+            .fold((0, 0), |(links, maybes), (_, link)| match link {
+                Link => (links + 1, maybes),
+                LMaybe => (links, maybes + 1),
+                Unlink => (links, maybes),
+            })
+    }
+
+    pub(super) fn write_edge(&mut self, edge: Edge, link: LinkType) -> bool {
+        match edge {
+            Edge::H(row, col) => self.write_hlink(row, col, link),
+            Edge::V(row, col) => self.write_vlink(row, col, link),
+        }
+    }
+
+    /// Narrows the vertex at `(row, col)` to the degrees its links still
+    /// allow, and decides its undecided links once the degree leaves a single
+    /// choice for them. Returns whether the board changed.
+    pub(super) fn constrain_vertex(
+        &mut self,
+        row: usize,
+        col: usize,
+    ) -> Result<bool, Contradiction> {
+        let (links, maybes) = self.vertex_degree(row, col);
+        let mut degrees = 0;
+        if links == 0 {
+            degrees |= Unvisited.to_mask();
+        }
+        if links <= 2 && links + maybes >= 2 {
+            degrees |= Visited.to_mask();
+        }
+        let degrees = self.vertices[row][col].to_mask() & degrees;
+        if degrees == 0 {
+            return Err(Contradiction);
+        }
+        let vertex = VertexType::from_mask(degrees);
+        let mut modified = self.write_vertex(row, col, vertex);
+        let fill = match vertex {
+            Unvisited => Some(Unlink),
+            Visited if links == 2 => Some(Unlink),
+            Visited if links + maybes == 2 => Some(Link),
+            _ => None,
+        };
+        if let Some(link) = fill {
+            let undecided = self
+                .vertex_edges(row, col)
+                .filter(|&(_, link)| link == LMaybe)
+                .map(|(edge, _)| edge)
+                .collect::<Vec<_>>();
+            for edge in undecided {
+                modified |= self.write_edge(edge, link);
+            }
+        }
+        Ok(modified)
+    }
+}
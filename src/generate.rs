@@ -0,0 +1,150 @@
+use crate::game::{Game, Rule};
+use crate::puzzle::parse_puzzle;
+
+/// A xorshift64* pseudo-random generator, good enough for puzzle generation.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// Grows a random region of cells whose boundary is a single loop, and
+/// returns for each cell whether it lies inside the loop.
+fn random_region(width: usize, height: usize, rng: &mut Rng) -> Vec<Vec<bool>> {
+    let mut inside = vec![vec![false; width]; height];
+    inside[rng.below(height)][rng.below(width)] = true;
+    let is_inside = |inside: &[Vec<bool>], row: isize, col: isize| {
+        row >= 0
+            && col >= 0
+            && (row as usize) < height
+            && (col as usize) < width
+            && inside[row as usize][col as usize]
+    };
+    // A cell can join the region if its inside neighbours form a single run
+    // around it that includes an orthogonal neighbour, so that the region
+    // stays simply connected and no vertex gets four boundary links.
+    let can_grow = |inside: &[Vec<bool>], row: usize, col: usize| {
+        const RING: [(isize, isize); 8] = [
+            (-1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+            (1, 0),
+            (1, -1),
+            (0, -1),
+            (-1, -1),
+        ];
+        let ring = RING.map(|(dr, dc)| is_inside(inside, row as isize + dr, col as isize + dc));
+        let runs = (0..8).filter(|&i| ring[i] && !ring[(i + 7) % 8]).count();
+        runs == 1 && (0..8).step_by(2).any(|i| ring[i])
+    };
+    let target = width * height * 3 / 5;
+    let mut size = 1;
+    while size < target {
+        let mut candidates = Vec::new();
+        for row in 0..height {
+            for col in 0..width {
+                if !inside[row][col] && can_grow(&inside, row, col) {
+                    candidates.push((row, col));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            break;
+        }
+        let (row, col) = candidates[rng.below(candidates.len())];
+        inside[row][col] = true;
+        size += 1;
+    }
+    inside
+}
+
+/// Generates a puzzle that `rules` solve without guessing, which ensures its
+/// solution is unique. Clues are removed from a fully clued random loop for
+/// as long as the rules still solve the puzzle.
+pub fn generate(width: usize, height: usize, rules: &[Rule], rng: &mut Rng) -> Game {
+    loop {
+        if let Some(game) = try_generate(width, height, rules, rng) {
+            return game;
+        }
+    }
+}
+
+fn solved_by_rules(puzzle: &str, rules: &[Rule]) -> bool {
+    let mut game = parse_puzzle(puzzle).unwrap();
+    game.propagate(rules).is_ok() && game.is_solved()
+}
+
+/// Clues of a random loop, every cell clued, as grid rows of '0'-'3'.
+pub fn clued_loop(width: usize, height: usize, rng: &mut Rng) -> Vec<Vec<char>> {
+    let inside = random_region(width, height, rng);
+    let at = |row: isize, col: isize| {
+        row >= 0
+            && col >= 0
+            && (row as usize) < height
+            && (col as usize) < width
+            && inside[row as usize][col as usize]
+    };
+    let mut clues = vec![vec!['.'; width]; height];
+    #[expect(clippy::needless_range_loop)]
+    for row in 0..height {
+        for col in 0..width {
+            let (r, c) = (row as isize, col as isize);
+            let here = at(r, c);
+            let links = [(r - 1, c), (r + 1, c), (r, c - 1), (r, c + 1)]
+                .into_iter()
+                .filter(|&(r, c)| at(r, c) != here)
+                .count();
+            clues[row][col] = char::from_digit(links as u32, 10).unwrap();
+        }
+    }
+    clues
+}
+
+/// Grid text of `clues`, rows separated by '/'.
+pub fn grid(clues: &[Vec<char>]) -> String {
+    clues
+        .iter()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns `None` if the rules cannot solve even the fully clued loop.
+fn try_generate(width: usize, height: usize, rules: &[Rule], rng: &mut Rng) -> Option<Game> {
+    let mut clues = clued_loop(width, height, rng);
+    if !solved_by_rules(&grid(&clues), rules) {
+        return None;
+    }
+    let mut cells = (0..height)
+        .flat_map(|row| (0..width).map(move |col| (row, col)))
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut cells);
+    for (row, col) in cells {
+        let clue = clues[row][col];
+        clues[row][col] = '.';
+        if !solved_by_rules(&grid(&clues), rules) {
+            clues[row][col] = clue;
+        }
+    }
+    Some(parse_puzzle(&grid(&clues)).unwrap())
+}
@@ -0,0 +1,169 @@
+use crate::game::{CellType, Checkpoint, ClueStatus, Game, LinkType, Rule};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+
+const HELP: &str = "arrows/hjkl: move  w/a/s/d: toggle top/left/bottom/right edge  \
+                    0-3/x: set/clear clue  ?: hint  p: propagate  u: undo  r: redo  q: quit";
+
+/// Puts the terminal in non-canonical, no-echo mode for as long as it lives,
+/// so that single key presses can be read. Does nothing if stdin is not a
+/// terminal.
+struct RawMode {
+    saved: Option<String>,
+}
+
+impl RawMode {
+    fn enable() -> RawMode {
+        let stty = |args: &[&str]| {
+            Command::new("stty")
+                .args(args)
+                .stdin(Stdio::inherit())
+                .stderr(Stdio::null())
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+        };
+        let saved = stty(&["-g"])
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+            .filter(|_| stty(&["-icanon", "-echo", "min", "1"]).is_some());
+        RawMode { saved }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved {
+            let _ = Command::new("stty")
+                .arg(saved)
+                .stdin(Stdio::inherit())
+                .status();
+        }
+    }
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+}
+
+fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let mut byte = [0];
+    loop {
+        if input.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        match byte[0] {
+            b'\n' | b'\r' => continue,
+            0x1b => {
+                let mut seq = [0; 2];
+                input.read_exact(&mut seq)?;
+                return Ok(match seq {
+                    [b'[', b'A'] => Some(Key::Up),
+                    [b'[', b'B'] => Some(Key::Down),
+                    [b'[', b'C'] => Some(Key::Right),
+                    [b'[', b'D'] => Some(Key::Left),
+                    _ => continue,
+                });
+            }
+            byte => return Ok(Some(Key::Char(byte as char))),
+        }
+    }
+}
+
+fn toggled(link: LinkType) -> LinkType {
+    match link {
+        LinkType::LMaybe => LinkType::Link,
+        LinkType::Link => LinkType::Unlink,
+        LinkType::Unlink => LinkType::LMaybe,
+    }
+}
+
+/// Runs an interactive session on `game` in the terminal, using `rules` (in
+/// priority order) for hints and propagation.
+pub fn play(game: &mut Game, rules: &[Rule]) -> io::Result<()> {
+    let _raw_mode = RawMode::enable();
+    let mut input = io::stdin().lock();
+    let mut out = io::stdout().lock();
+    let (mut row, mut col) = (1, 1);
+    let mut history: Vec<Checkpoint> = Vec::new();
+    let mut message = String::new();
+    loop {
+        write!(out, "\x1b[2J\x1b[H")?;
+        game.render_unicode(&mut out, true, Some((row, col)))?;
+        let violated = (1..game.height() - 1)
+            .flat_map(|row| (1..game.width() - 1).map(move |col| (row, col)))
+            .filter(|&(row, col)| game.clue_status(row, col) == ClueStatus::Violated)
+            .count();
+        writeln!(out)?;
+        writeln!(out, "Violated clues: {violated}")?;
+        writeln!(out, "{message}")?;
+        writeln!(out, "{HELP}")?;
+        out.flush()?;
+        let Some(key) = read_key(&mut input)? else {
+            break;
+        };
+        message.clear();
+        let checkpoint = game.checkpoint();
+        match key {
+            Key::Up | Key::Char('k') => row = (row - 1).max(1),
+            Key::Down | Key::Char('j') => row = (row + 1).min(game.height() - 2),
+            Key::Left | Key::Char('h') => col = (col - 1).max(1),
+            Key::Right | Key::Char('l') => col = (col + 1).min(game.width() - 2),
+            Key::Char('w') => game.set_hlink(row, col, toggled(game.hlink(row, col))),
+            Key::Char('s') => game.set_hlink(row + 1, col, toggled(game.hlink(row + 1, col))),
+            Key::Char('a') => game.set_vlink(row, col, toggled(game.vlink(row, col))),
+            Key::Char('d') => game.set_vlink(row, col + 1, toggled(game.vlink(row, col + 1))),
+            Key::Char(c @ ('0'..='3' | 'x')) => {
+                let cell = match c {
+                    '0' => CellType::Zero,
+                    '1' => CellType::One,
+                    '2' => CellType::Two,
+                    '3' => CellType::Three,
+                    _ => CellType::Empty,
+                };
+                game.set_cell(row, col, cell);
+            }
+            Key::Char('?') => {
+                message = match game.apply_any_rule(rules) {
+                    Ok(Some((index, row_s, col_s))) => {
+                        format!("Applied \"{}\" at ({row_s}, {col_s})", rules[index].name())
+                    }
+                    Ok(None) => "No rule applies".to_owned(),
+                    Err(_) => "Contradiction!".to_owned(),
+                }
+            }
+            Key::Char('p') => {
+                message = match game.propagate(rules) {
+                    Ok(progress) => format!(
+                        "Applied {} rules, and the built-in constraints changed {} elements",
+                        progress.firings, progress.builtin_changes
+                    ),
+                    Err(_) => "Contradiction!".to_owned(),
+                }
+            }
+            Key::Char('u') => match history.pop() {
+                Some(previous) => game.rollback(previous),
+                None => message = "Nothing to undo".to_owned(),
+            },
+            Key::Char('r') => {
+                if game.redo() {
+                    history.push(checkpoint);
+                } else {
+                    message = "Nothing to redo".to_owned();
+                }
+            }
+            Key::Char('q') => break,
+            Key::Char(_) => message = "Unknown key".to_owned(),
+        }
+        if matches!(
+            key,
+            Key::Char('w' | 's' | 'a' | 'd' | '0'..='3' | 'x' | '?' | 'p')
+        ) {
+            history.push(checkpoint);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use crate::game::Game;
+
+const MIN_SIZE: usize = 5;
+
+fn check_size(width: usize, height: usize) -> Result<(), String> {
+    if width < MIN_SIZE || height < MIN_SIZE {
+        return Err(format!(
+            "puzzles must be at least {MIN_SIZE}x{MIN_SIZE}, found {width}x{height}"
+        ));
+    }
+    Ok(())
+}
+
+fn parse_id(size: &str, task: &str) -> Result<Game, String> {
+    let size = size.trim();
+    let size = size.strip_suffix("t0").unwrap_or(size);
+    let (width, height) = size
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or_else(|| format!("invalid puzzle size {size:?}"))?;
+    check_size(width, height)?;
+    let task = task.trim();
+    let mut count = 0;
+    for c in task.chars() {
+        count += match c {
+            '0'..='3' => 1,
+            'a'..='z' => (c as u8 - b'a') as usize + 1,
+            _ => return Err(format!("unexpected character {c:?} in puzzle")),
+        };
+    }
+    if count != width * height {
+        return Err(format!(
+            "puzzle describes {count} cells, expected {}",
+            width * height
+        ));
+    }
+    Ok(Game::new(width, height, task))
+}
+
+fn parse_grid(rows: &[&str]) -> Result<Game, String> {
+    let height = rows.len();
+    let width = rows[0].chars().count();
+    if let Some(row) = rows.iter().find(|row| row.chars().count() != width) {
+        return Err(format!(
+            "grid row {row:?} has {} cells, expected {width}",
+            row.chars().count()
+        ));
+    }
+    check_size(width, height)?;
+    let mut task = String::new();
+    for c in rows.iter().flat_map(|row| row.chars()) {
+        match c {
+            '0'..='3' => task.push(c),
+            '.' => task.push('a'),
+            _ => return Err(format!("unexpected character {c:?} in grid")),
+        }
+    }
+    Ok(Game::new(width, height, &task))
+}
+
+/// Parses a puzzle, given either as a game ID in the style of Simon Tatham's
+/// Loopy, `WxH:task` (an optional `t0` grid type after the size is accepted)
+/// with `task` the clue encoding of [`Game::new`], or as a grid with one row
+/// per line (or rows separated by `/`), `0`-`3` for clues and `.` for blanks.
+pub fn parse_puzzle(text: &str) -> Result<Game, String> {
+    let text = text.trim();
+    if let Some((size, task)) = text.split_once(':') {
+        return parse_id(size, task);
+    }
+    let rows = text
+        .split(['\n', '/'])
+        .map(str::trim)
+        .filter(|row| !row.is_empty())
+        .collect::<Vec<_>>();
+    if rows.is_empty() {
+        return Err("empty puzzle".to_owned());
+    }
+    parse_grid(&rows)
+}
+
+/// Formats the clues of a game as a `WxH:task` game ID.
+pub fn puzzle_id(game: &Game) -> String {
+    format!("{}x{}:{}", game.width() - 2, game.height() - 2, game.task())
+}
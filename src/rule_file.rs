@@ -0,0 +1,409 @@
+use crate::game::{Rule, Symmetry};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Rule files loaded when none are given on the command line, with the
+/// priority of their rules that do not declare one.
+pub const DEFAULT_RULE_FILES: [(&str, i32); 2] = [
+    ("./assets/unconditional_rules.txt", 100),
+    ("./assets/basic_rules.txt", 200),
+];
+
+/// A rule as written in a rule file, together with its priority, its tags
+/// and its distinct images under its symmetry. `variants[0]` is the rule as
+/// written.
+#[derive(Debug)]
+pub struct RuleEntry {
+    /// Path of the rule file defining the rule.
+    pub file: String,
+    pub priority: i32,
+    pub tags: Vec<String>,
+    pub symmetry: Symmetry,
+    pub variants: Vec<Rule>,
+}
+
+impl RuleEntry {
+    pub fn rule(&self) -> &Rule {
+        &self.variants[0]
+    }
+}
+
+/// A rule of a rule file before parsing its window.
+struct RuleText {
+    name: String,
+    content: String,
+    priority: i32,
+    tags: Vec<String>,
+    symmetry: Symmetry,
+}
+
+/// Parses the header of a rule, its name followed by `[DIRECTIVE] value`
+/// pairs, into `text`.
+fn parse_header(header: &str, text: &mut RuleText) -> Result<(), String> {
+    let mut parts = header.split('[');
+    text.name = parts.next().unwrap_or_default().trim().to_owned();
+    let mut seen = Vec::new();
+    for part in parts {
+        let Some((directive, value)) = part.split_once(']') else {
+            return Err(format!("rule \"{}\" has an unclosed [", text.name));
+        };
+        let value = value.trim();
+        if seen.contains(&directive) {
+            return Err(format!(
+                "rule \"{}\" has a duplicate [{directive}]",
+                text.name
+            ));
+        }
+        seen.push(directive);
+        match directive {
+            "PRIORITY" => {
+                text.priority = value.parse().map_err(|_| {
+                    format!("rule \"{}\" has an invalid priority {value:?}", text.name)
+                })?;
+            }
+            "TAGS" => {
+                let tags = value.split([',', ' ']).filter(|tag| !tag.is_empty());
+                text.tags.extend(tags.map(str::to_owned));
+            }
+            "SYMMETRY" => {
+                text.symmetry =
+                    Symmetry::parse(value).map_err(|e| format!("rule \"{}\": {e}", text.name))?;
+            }
+            _ => {
+                return Err(format!(
+                    "rule \"{}\" has an unknown directive [{directive}]",
+                    text.name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits the text of a rule file into its rules, which get
+/// `default_priority` unless they declare one and `default_tags` besides the
+/// tags they declare.
+fn read_rule_texts(
+    text: &str,
+    default_priority: i32,
+    default_tags: &[String],
+) -> Result<Vec<RuleText>, String> {
+    text.trim()
+        .split("[NAME]")
+        .filter_map(|s| {
+            let strs = s.split("[BEGIN]").collect::<Vec<_>>();
+            if strs.len() != 2 {
+                return None;
+            }
+            let mut text = RuleText {
+                name: String::new(),
+                content: strs[1].split("[END]").collect::<Vec<_>>()[0]
+                    .trim()
+                    .to_owned(),
+                priority: default_priority,
+                tags: default_tags.to_vec(),
+                symmetry: Symmetry::default(),
+            };
+            Some(parse_header(strs[0], &mut text).map(|()| text))
+        })
+        .collect()
+}
+
+/// Tag of every rule of the file at `path`: its name without extension nor
+/// `_rules` suffix, e.g. `basic` for `assets/basic_rules.txt`.
+fn file_tag(path: &str) -> String {
+    let stem = Path::new(path)
+        .file_stem()
+        .map_or(path.into(), |stem| stem.to_string_lossy());
+    stem.strip_suffix("_rules").unwrap_or(&stem).to_owned()
+}
+
+/// Rule files being read, innermost last, and every file read so far, by
+/// canonical path.
+#[derive(Default)]
+struct Includes {
+    stack: Vec<(PathBuf, String)>,
+    loaded: HashSet<PathBuf>,
+}
+
+/// Reads and parses the rule file at `path` into `entries`, unless it was
+/// already read, together with the files it includes with `[INCLUDE] path`,
+/// relative to the including file. Rules without a `[PRIORITY]` get
+/// `default_priority`, and those without a `[SYMMETRY]` all their images
+/// under rotations and reflections. Rules are tagged with `tags`, those of
+/// the including files, with the name of the file and with the tags declared
+/// with `[TAGS]`.
+fn read_rules_into(
+    path: &str,
+    default_priority: i32,
+    tags: &[String],
+    includes: &mut Includes,
+    entries: &mut Vec<RuleEntry>,
+) -> Result<(), String> {
+    let canonical = fs::canonicalize(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    if includes.stack.iter().any(|(file, _)| *file == canonical) {
+        let chain = includes.stack.iter().map(|(_, path)| path.as_str());
+        return Err(format!(
+            "include cycle: {} -> {path}",
+            chain.collect::<Vec<_>>().join(" -> ")
+        ));
+    }
+    if !includes.loaded.insert(canonical.clone()) {
+        return Ok(());
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    let mut tags = tags.to_vec();
+    tags.push(file_tag(path));
+    includes.stack.push((canonical, path.to_owned()));
+    // Rules are parsed a chunk at a time, a chunk ending at each [INCLUDE],
+    // so that included rules come at the place of the directive.
+    let mut chunk = String::new();
+    for line in text.lines().chain(["[INCLUDE]"]) {
+        let Some(include) = line.trim().strip_prefix("[INCLUDE]") else {
+            chunk.push_str(line);
+            chunk.push('\n');
+            continue;
+        };
+        if chunk.rfind("[NAME]") > chunk.rfind("[END]") {
+            return Err(format!("{path}: rule without [END] before [INCLUDE]"));
+        }
+        for text in
+            read_rule_texts(&chunk, default_priority, &tags).map_err(|e| format!("{path}: {e}"))?
+        {
+            let rule = Rule::new(&text.name, &text.content)
+                .map_err(|e| format!("{path}: {e}"))?
+                .with_priority(text.priority);
+            entries.push(RuleEntry {
+                file: path.to_owned(),
+                priority: text.priority,
+                tags: text.tags,
+                symmetry: text.symmetry,
+                variants: rule.orbit(text.symmetry),
+            });
+        }
+        chunk.clear();
+        let include = include.trim();
+        if !include.is_empty() {
+            let included = Path::new(path).with_file_name(include);
+            read_rules_into(
+                &included.to_string_lossy(),
+                default_priority,
+                &tags,
+                includes,
+                entries,
+            )?;
+        }
+    }
+    includes.stack.pop();
+    Ok(())
+}
+
+/// Reads several rule files, each once, and sorts their rules by priority,
+/// keeping the file order among rules of equal priority.
+pub fn load_rule_files<'a>(
+    files: impl IntoIterator<Item = (&'a str, i32)>,
+) -> Result<Vec<RuleEntry>, String> {
+    let mut entries = Vec::new();
+    let mut includes = Includes::default();
+    for (path, default_priority) in files {
+        read_rules_into(path, default_priority, &[], &mut includes, &mut entries)?;
+    }
+    entries.sort_by_key(|entry| entry.priority);
+    Ok(entries)
+}
+
+/// Whether the name of `entries[index]` is already taken by an earlier entry,
+/// and where.
+fn duplicate_name(entries: &[RuleEntry], index: usize) -> Option<String> {
+    let entry = &entries[index];
+    let name = entry.rule().name();
+    let other = entries[..index]
+        .iter()
+        .find(|other| other.rule().name() == name)?;
+    Some(if other.file == entry.file {
+        format!("{}: rule {name:?} is defined twice", entry.file)
+    } else {
+        format!(
+            "rule {name:?} is defined in both {} and {}",
+            other.file, entry.file
+        )
+    })
+}
+
+/// Checks that rule names are unique across all the rule files.
+pub fn check_names(entries: &[RuleEntry]) -> Result<(), String> {
+    match (0..entries.len()).find_map(|index| duplicate_name(entries, index)) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Problems of `entries` that do not prevent using them: those of
+/// [`Rule::lint`], duplicate names and rules which add nothing to another
+/// rule, as each of their variants is one of the variants of the other, up
+/// to margins and labels, see [`Rule::canonicalize`]. Of two rules with the
+/// same variants, the later one is reported.
+pub fn lint(entries: &[RuleEntry]) -> Vec<String> {
+    let variant_ids = entries
+        .iter()
+        .map(|entry| {
+            entry
+                .variants
+                .iter()
+                .map(|variant| variant.canonical_hash(Symmetry::Identity))
+                .collect::<HashSet<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut problems = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let name = entry.rule().name();
+        for problem in entry.rule().lint() {
+            problems.push(format!("{}: rule {name:?} {problem}", entry.file));
+        }
+        problems.extend(duplicate_name(entries, index));
+        let ids = &variant_ids[index];
+        let covering = (0..entries.len()).find(|&other| {
+            other != index
+                && ids.is_subset(&variant_ids[other])
+                && (other < index || ids != &variant_ids[other])
+        });
+        if let Some(other) = covering {
+            let relation = if ids == &variant_ids[other] {
+                "is the same as"
+            } else {
+                "adds no variant to"
+            };
+            problems.push(format!(
+                "{}: rule {name:?} {relation} rule {:?} of {} up to margins, symmetry and labels",
+                entry.file,
+                entries[other].rule().name(),
+                entries[other].file
+            ));
+        }
+    }
+    problems
+}
+
+/// Which of the loaded rules the solver uses.
+#[derive(Clone, Default, Debug)]
+pub struct RuleSelection {
+    /// Tags of the rules to use, or empty to use rules of any tag.
+    pub enabled: Vec<String>,
+    /// Tags of the rules to leave out, whatever their other tags.
+    pub disabled: Vec<String>,
+    /// Priority above which rules are left out.
+    pub max_priority: Option<i32>,
+}
+
+impl RuleSelection {
+    pub fn selects(&self, entry: &RuleEntry) -> bool {
+        let has = |tags: &[String]| entry.tags.iter().any(|tag| tags.contains(tag));
+        (self.enabled.is_empty() || has(&self.enabled))
+            && !has(&self.disabled)
+            && self.max_priority.is_none_or(|max| entry.priority <= max)
+    }
+
+    /// Keeps the entries selected, after checking that every tag named
+    /// belongs to some rule, as a misspelt tag would silently select nothing.
+    pub fn apply(&self, entries: Vec<RuleEntry>) -> Result<Vec<RuleEntry>, String> {
+        for tag in self.enabled.iter().chain(&self.disabled) {
+            if !entries.iter().any(|entry| entry.tags.contains(tag)) {
+                return Err(format!("no rule is tagged {tag:?}"));
+            }
+        }
+        Ok(entries
+            .into_iter()
+            .filter(|entry| self.selects(entry))
+            .collect())
+    }
+}
+
+/// All variants of all entries, in priority order, as expected by
+/// [`crate::game::Game::propagate`].
+pub fn flatten(entries: &[RuleEntry]) -> Vec<Rule> {
+    entries
+        .iter()
+        .flat_map(|entry| entry.variants.iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// Empty directory for the rule files of the test `name`.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("slitherlinker-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        dir
+    }
+
+    /// Writes `lines` to `dir/file`, and returns its path.
+    fn write(dir: &Path, file: &str, lines: &[&str]) -> String {
+        let path = dir.join(file);
+        fs::write(&path, lines.join("\n")).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// A rule file entry for a rule on a clue 0, named `name`.
+    fn rule(name: &str) -> String {
+        format!(
+            "[NAME] {name}\n[BEGIN]\n+.......+\n.       .\n.   0   .\n.       .\n+.......+\n=>\n\
+             +       +\n         \n    0    \n         \n+       +\n[END]"
+        )
+    }
+
+    fn names(entries: &[RuleEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.rule().name()).collect()
+    }
+
+    #[test]
+    fn included_rules_come_at_the_place_of_the_include() {
+        let dir = test_dir("include");
+        let (first, middle, last) = (rule("First"), rule("Middle"), rule("Last"));
+        write(&dir, "sub/inner_rules.txt", &[&middle]);
+        let outer = write(
+            &dir,
+            "outer_rules.txt",
+            &[
+                &first,
+                "[INCLUDE] sub/inner_rules.txt",
+                "[INCLUDE] sub/inner_rules.txt",
+                &last,
+            ],
+        );
+        let entries = load_rule_files([(outer.as_str(), 200), (outer.as_str(), 200)]).unwrap();
+        assert_eq!(names(&entries), ["First", "Middle", "Last"]);
+        assert_eq!(entries[1].tags, ["outer", "inner"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let dir = test_dir("cycle");
+        let a = write(&dir, "a.txt", &["[INCLUDE] b.txt"]);
+        write(&dir, "b.txt", &["[INCLUDE] a.txt"]);
+        let b = dir.join("b.txt").to_string_lossy().into_owned();
+        let e = load_rule_files([(a.as_str(), 200)]).unwrap_err();
+        assert_eq!(e, format!("include cycle: {a} -> {b} -> {a}"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn names_are_unique_across_files() {
+        let dir = test_dir("names");
+        let same = rule("Same");
+        let x = write(&dir, "x.txt", &[&same]);
+        let y = write(&dir, "y.txt", &[&same]);
+        let entries = load_rule_files([(x.as_str(), 200), (y.as_str(), 200)]).unwrap();
+        assert_eq!(
+            check_names(&entries),
+            Err(format!("rule \"Same\" is defined in both {x} and {y}"))
+        );
+        assert_eq!(check_names(&entries[..1]), Ok(()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+use crate::game::{Cnf, Game};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs, process};
+
+/// How to find models of a [`Cnf`].
+#[derive(Clone, Debug)]
+pub enum SatSolver {
+    /// The DPLL solver below.
+    Builtin,
+    /// A program taking `input output` file arguments and writing its model
+    /// the way MiniSat does.
+    External(String),
+}
+
+impl SatSolver {
+    pub fn parse(name: &str) -> SatSolver {
+        match name {
+            "builtin" => SatSolver::Builtin,
+            command => SatSolver::External(command.to_owned()),
+        }
+    }
+
+    /// Returns a model, `model[i]` being the value of variable `i + 1`, or
+    /// `None` if the formula is unsatisfiable.
+    pub fn solve(&self, cnf: &Cnf) -> Result<Option<Vec<bool>>, String> {
+        match self {
+            SatSolver::Builtin => Ok(Dpll::new(cnf).solve()),
+            SatSolver::External(command) => solve_external(command, cnf),
+        }
+    }
+}
+
+/// Index of a literal in per-literal tables.
+fn literal_index(literal: i32) -> usize {
+    2 * (literal.unsigned_abs() as usize - 1) + (literal < 0) as usize
+}
+
+struct Dpll<'a> {
+    clauses: &'a [Vec<i32>],
+    /// Clauses in which each literal occurs.
+    occurrences: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    /// Literals made true, in order.
+    trail: Vec<i32>,
+    /// Number of literals of the trail whose consequences were propagated.
+    propagated: usize,
+}
+
+impl<'a> Dpll<'a> {
+    fn new(cnf: &'a Cnf) -> Dpll<'a> {
+        let mut occurrences = vec![Vec::new(); 2 * cnf.variables];
+        for (index, clause) in cnf.clauses.iter().enumerate() {
+            for &literal in clause {
+                occurrences[literal_index(literal)].push(index);
+            }
+        }
+        Dpll {
+            clauses: &cnf.clauses,
+            occurrences,
+            values: vec![None; cnf.variables],
+            trail: Vec::new(),
+            propagated: 0,
+        }
+    }
+
+    fn value(&self, literal: i32) -> Option<bool> {
+        self.values[literal.unsigned_abs() as usize - 1].map(|value| value == (literal > 0))
+    }
+
+    fn assign(&mut self, literal: i32) {
+        self.values[literal.unsigned_abs() as usize - 1] = Some(literal > 0);
+        self.trail.push(literal);
+    }
+
+    fn undo(&mut self, len: usize) {
+        for literal in self.trail.drain(len..) {
+            self.values[literal.unsigned_abs() as usize - 1] = None;
+        }
+        self.propagated = self.propagated.min(len);
+    }
+
+    /// Checks `clause`, assigning its last literal if the others are false.
+    /// Returns `false` on a conflict.
+    fn check_clause(&mut self, clause: usize) -> bool {
+        let mut unassigned = None;
+        for &literal in &self.clauses[clause] {
+            match self.value(literal) {
+                Some(true) => return true,
+                Some(false) => (),
+                None if unassigned.is_some() => return true,
+                None => unassigned = Some(literal),
+            }
+        }
+        match unassigned {
+            Some(literal) => {
+                self.assign(literal);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unit propagation. Returns `false` on a conflict.
+    fn propagate(&mut self) -> bool {
+        while self.propagated < self.trail.len() {
+            let literal = self.trail[self.propagated];
+            self.propagated += 1;
+            for index in 0..self.occurrences[literal_index(-literal)].len() {
+                let clause = self.occurrences[literal_index(-literal)][index];
+                if !self.check_clause(clause) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn solve(mut self) -> Option<Vec<bool>> {
+        for clause in 0..self.clauses.len() {
+            if !self.check_clause(clause) {
+                return None;
+            }
+        }
+        // Decisions as the length of the trail before them, the literal
+        // decided and whether it is already the second try.
+        let mut decisions: Vec<(usize, i32, bool)> = Vec::new();
+        loop {
+            if !self.propagate() {
+                loop {
+                    let (len, literal, flipped) = decisions.pop()?;
+                    self.undo(len);
+                    if !flipped {
+                        decisions.push((len, -literal, true));
+                        self.assign(-literal);
+                        break;
+                    }
+                }
+                continue;
+            }
+            let Some(variable) = self.values.iter().position(Option::is_none) else {
+                return Some(self.values.iter().map(|value| value.unwrap()).collect());
+            };
+            // Most links of a loop puzzle are unlinked, so try false first.
+            let literal = -(variable as i32 + 1);
+            decisions.push((self.trail.len(), literal, false));
+            self.assign(literal);
+        }
+    }
+}
+
+/// A file of the temporary directory, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Creates a new file named after `base` and `extension`, failing rather
+    /// than reusing a file that already exists, as another user could have
+    /// planted one there.
+    fn create(base: &str, extension: &str) -> Result<(TempFile, File), String> {
+        let path = env::temp_dir().join(format!("{base}.{extension}"));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("cannot create {}: {e}", path.display()))?;
+        Ok((TempFile(path), file))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn solve_external(command: &str, cnf: &Cnf) -> Result<Option<Vec<bool>>, String> {
+    // Files are named after the process and the call, so that calls do not
+    // overwrite each other's files.
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let call = CALLS.fetch_add(1, Ordering::Relaxed);
+    let base = format!("slitherlinker-{}-{call}", process::id());
+    let (input, input_file) = TempFile::create(&base, "cnf")?;
+    let (output, _) = TempFile::create(&base, "out")?;
+    let mut writer = BufWriter::new(input_file);
+    cnf.write_dimacs(&mut writer)
+        .and_then(|()| writer.flush())
+        .map_err(|e| format!("cannot write {}: {e}", input.0.display()))?;
+    drop(writer);
+    let status = Command::new(command)
+        .arg(&input.0)
+        .arg(&output.0)
+        .output()
+        .map_err(|e| format!("cannot run {command}: {e}"))?
+        .status;
+    let result = fs::read_to_string(&output.0)
+        .map_err(|e| format!("cannot read the result of {command}: {e}"))?;
+    if result.trim().is_empty() {
+        return Err(format!("{command} wrote no result ({status})"));
+    }
+    let mut lines = result.lines();
+    match lines.next().map(str::trim) {
+        Some("UNSAT") => return Ok(None),
+        Some("SAT") => (),
+        _ => return Err(format!("unexpected output from {command}")),
+    }
+    let mut model = vec![false; cnf.variables];
+    for literal in lines.flat_map(str::split_whitespace) {
+        let literal = literal
+            .parse::<i32>()
+            .map_err(|_| format!("unexpected literal {literal:?} from {command}"))?;
+        if literal > 0 && literal as usize <= cnf.variables {
+            model[literal as usize - 1] = true;
+        }
+    }
+    Ok(Some(model))
+}
+
+/// Finds up to `limit` solutions of `game` with `solver`, adding clauses
+/// against several loops, and against solutions already found, until the
+/// models decode to single loops.
+pub fn sat_solutions(game: &Game, solver: &SatSolver, limit: usize) -> Result<Vec<Game>, String> {
+    let mut cnf = game.to_cnf();
+    let mut solutions = Vec::new();
+    while solutions.len() < limit {
+        let Some(model) = solver.solve(&cnf)? else {
+            break;
+        };
+        let mut solution = game.clone();
+        solution.apply_model(&model);
+        let cuts = solution.loop_cuts();
+        if cuts.is_empty() {
+            cnf.clauses.push(
+                model
+                    .iter()
+                    .enumerate()
+                    .map(|(variable, &value)| {
+                        let variable = variable as i32 + 1;
+                        if value { -variable } else { variable }
+                    })
+                    .collect(),
+            );
+            solutions.push(solution);
+        } else {
+            cnf.clauses.extend(cuts);
+        }
+    }
+    Ok(solutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::parse_puzzle;
+
+    fn solve(variables: usize, clauses: &[&[i32]]) -> Option<Vec<bool>> {
+        let cnf = Cnf {
+            variables,
+            clauses: clauses.iter().map(|clause| clause.to_vec()).collect(),
+        };
+        Dpll::new(&cnf).solve()
+    }
+
+    #[test]
+    fn dpll_follows_unit_clauses() {
+        assert_eq!(solve(3, &[&[1], &[-2], &[2, 3], &[-1, 2, -3]]), None);
+        assert_eq!(
+            solve(3, &[&[1], &[-2], &[2, 3]]),
+            Some(vec![true, false, true])
+        );
+    }
+
+    #[test]
+    fn dpll_finds_models_that_need_decisions() {
+        let clauses: &[&[i32]] = &[&[1, 2], &[-1, 3], &[-2, -3], &[1, -3]];
+        let model = solve(3, clauses).unwrap();
+        for clause in clauses {
+            assert!(
+                clause
+                    .iter()
+                    .any(|&literal| model[literal.unsigned_abs() as usize - 1] == (literal > 0))
+            );
+        }
+    }
+
+    #[test]
+    fn dpll_reports_unsatisfiable_formulas() {
+        assert_eq!(solve(1, &[&[]]), None);
+        assert_eq!(solve(1, &[&[1], &[-1]]), None);
+        assert_eq!(solve(2, &[&[1, 2], &[1, -2], &[-1, 2], &[-1, -2]]), None);
+    }
+
+    #[test]
+    fn dpll_satisfies_formulas_without_clauses() {
+        assert_eq!(solve(2, &[]), Some(vec![false, false]));
+    }
+
+    #[test]
+    fn temporary_files_are_new_and_removed() {
+        let base = format!("slitherlinker-{}-test", process::id());
+        let (file, _) = TempFile::create(&base, "cnf").unwrap();
+        let path = file.0.clone();
+        assert!(TempFile::create(&base, "cnf").is_err());
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn sat_solutions_are_single_loops() {
+        let game = parse_puzzle("5x5:y").unwrap();
+        let solutions = sat_solutions(&game, &SatSolver::Builtin, 2).unwrap();
+        assert_eq!(solutions.len(), 2);
+        assert!(
+            solutions
+                .iter()
+                .all(|solution| solution.loop_cuts().is_empty())
+        );
+    }
+}